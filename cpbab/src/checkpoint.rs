@@ -0,0 +1,244 @@
+//! Checkpoints of a running search, to resume long runs after an interruption.
+//!
+//! A graph of the search is stored as the disjunctions it has fixed together with the heads and tails
+//! of its nodes. It is restored by fixing those disjunctions on the graph the search started from.
+//! The checkpoint holds the root, the incumbent, the open nodes in the order they are explored,
+//! the nogoods and the counters of the search. The transposition table is not stored, it only skips
+//! nodes that are pruned anyway. Random tie-breaking is re-seeded from the node count at every checkpoint,
+//! so a resumed search makes the same random choices.
+//!
+//! The file is plain text, one record per line:
+//! ```text
+//! cpbab checkpoint
+//! bounds <max makespan> <upper bound>
+//! counters <node evaluations> <runs> <evaluations of the current run>
+//! root
+//! fixed 1-5 3-7
+//! heads 0 0 4
+//! tails 9 6 0
+//! best 12
+//! fixed ..
+//! heads ..
+//! tails ..
+//! nogood 1-5 7-3
+//! open 1-5
+//! fixed ..
+//! heads ..
+//! tails ..
+//! ```
+use std::fs::File;
+use std::io::{ self, BufRead, BufReader, BufWriter, Write };
+use std::path::{ Path, PathBuf };
+
+use disjunctgraph::{ ConstrainedNode, Graph, NodeId };
+
+use crate::nogood::Decision;
+
+/// Write a checkpoint to `path` every `interval` node evaluations, the search stops with an error when that fails.
+#[derive(Clone, Debug)]
+pub struct Checkpointing {
+    pub path: PathBuf,
+    pub interval: usize,
+}
+
+/// The fixed disjunctions of a graph and the heads and tails of its nodes
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphState {
+    pub fixed: Vec<Decision>,
+    pub heads: Vec<u32>,
+    pub tails: Vec<u32>,
+}
+
+/// A node that is still to be explored, with the decisions that led to it
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpenNode {
+    pub decisions: Vec<Decision>,
+    pub graph: GraphState,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    /// The makespan the search was started with
+    pub max_makespan: u32,
+    pub upper_bound: u32,
+    pub node_evaluations: usize,
+    pub runs: usize,
+    pub run_evaluations: usize,
+    /// The root of the current run, tightened on every restart
+    pub root: GraphState,
+    pub best: Option<(GraphState, u32)>,
+    pub nogoods: Vec<Vec<Decision>>,
+    /// Open nodes of the current run, the first is explored first
+    pub open: Vec<OpenNode>,
+}
+
+impl GraphState {
+    pub fn new<G: Graph>(graph: &G) -> Self where G::Node: ConstrainedNode {
+        let fixed = graph.nodes().iter()
+            .flat_map(|node| graph.successors(node).map(move |other| (node.id(), other.id())))
+            .filter(|(node_1, node_2)| graph.has_fixed_disjunction(node_1, node_2))
+            .collect();
+
+        GraphState {
+            fixed,
+            heads: graph.nodes().iter().map(|node| node.head()).collect(),
+            tails: graph.nodes().iter().map(|node| node.tail()).collect(),
+        }
+    }
+
+    /// Fix the disjunctions on a copy of the graph the search started from and set the heads and tails.
+    pub fn restore<G: Graph + Clone>(&self, base: &G) -> Result<G, String> where G::Node: ConstrainedNode {
+        let mut graph = base.clone();
+        if self.heads.len() != graph.nodes().len() || self.tails.len() != graph.nodes().len() {
+            return Err(format!("Checkpoint has {} heads and {} tails for {} nodes", self.heads.len(), self.tails.len(), graph.nodes().len()));
+        }
+
+        for (node_1, node_2) in &self.fixed {
+            if *node_1 >= graph.nodes().len() || *node_2 >= graph.nodes().len() {
+                return Err(format!("Checkpoint fixes {} -> {}, which is not in the graph", node_1, node_2));
+            }
+            if !graph.has_precedence(node_1, node_2) {
+                graph.fix_disjunction(node_1, node_2).map_err(|e| format!("Could not fix disjunction {} -> {}, {:?}", node_1, node_2, e))?;
+            }
+        }
+
+        for (id, (head, tail)) in self.heads.iter().zip(&self.tails).enumerate() {
+            graph[id].set_head(*head);
+            graph[id].set_tail(*tail);
+        }
+        Ok(graph)
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "fixed{}", decisions(&self.fixed))?;
+        writeln!(writer, "heads{}", self.heads.iter().map(|h| format!(" {}", h)).collect::<String>())?;
+        writeln!(writer, "tails{}", self.tails.iter().map(|t| format!(" {}", t)).collect::<String>())
+    }
+
+    fn read(lines: &mut impl Iterator<Item = String>) -> Result<Self, String> {
+        Ok(GraphState {
+            fixed: parse_decisions(&record(lines, "fixed")?)?,
+            heads: parse_numbers(&record(lines, "heads")?)?,
+            tails: parse_numbers(&record(lines, "tails")?)?,
+        })
+    }
+}
+
+impl Checkpoint {
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "cpbab checkpoint")?;
+        writeln!(writer, "bounds {} {}", self.max_makespan, self.upper_bound)?;
+        writeln!(writer, "counters {} {} {}", self.node_evaluations, self.runs, self.run_evaluations)?;
+        writeln!(writer, "root")?;
+        self.root.write(writer)?;
+        match &self.best {
+            Some((best, length)) => {
+                writeln!(writer, "best {}", length)?;
+                best.write(writer)?;
+            },
+            None => writeln!(writer, "best")?
+        }
+        for nogood in &self.nogoods {
+            writeln!(writer, "nogood{}", decisions(nogood))?;
+        }
+        for node in &self.open {
+            writeln!(writer, "open{}", decisions(&node.decisions))?;
+            node.graph.write(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Write to a temporary file first, an interruption while writing leaves the previous checkpoint intact.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let temporary = path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&temporary)?);
+            self.write(&mut writer)?;
+            writer.flush()?;
+        }
+        std::fs::rename(temporary, path)
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, String> {
+        let lines = reader.lines().collect::<io::Result<Vec<_>>>().map_err(|e| format!("Could not read checkpoint: {}", e))?;
+        let mut lines = lines.into_iter().filter(|line| !line.trim().is_empty()).peekable();
+
+        if lines.next().as_deref() != Some("cpbab checkpoint") {
+            return Err("Not a checkpoint".to_owned());
+        }
+        let bounds = parse_numbers::<u32>(&record(&mut lines, "bounds")?)?;
+        let counters = parse_numbers::<usize>(&record(&mut lines, "counters")?)?;
+        if bounds.len() != 2 || counters.len() != 3 {
+            return Err("Expected two bounds and three counters".to_owned());
+        }
+        record(&mut lines, "root")?;
+        let root = GraphState::read(&mut lines)?;
+
+        let best = record(&mut lines, "best")?;
+        let best = match best.trim() {
+            "" => None,
+            length => {
+                let length = length.parse::<u32>().map_err(|_| format!("Invalid length of the best schedule: {}", length))?;
+                Some((GraphState::read(&mut lines)?, length))
+            }
+        };
+
+        let mut nogoods = Vec::new();
+        while lines.peek().is_some_and(|line| line.starts_with("nogood")) {
+            nogoods.push(parse_decisions(&record(&mut lines, "nogood")?)?);
+        }
+
+        let mut open = Vec::new();
+        while lines.peek().is_some() {
+            let decisions = parse_decisions(&record(&mut lines, "open")?)?;
+            open.push(OpenNode { decisions, graph: GraphState::read(&mut lines)? });
+        }
+
+        Ok(Checkpoint {
+            max_makespan: bounds[0],
+            upper_bound: bounds[1],
+            node_evaluations: counters[0],
+            runs: counters[1],
+            run_evaluations: counters[2],
+            root, best, nogoods, open
+        })
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| format!("Could not read checkpoint {}: {}", path.display(), e))?;
+        Checkpoint::from_reader(BufReader::new(file))
+    }
+}
+
+fn decisions(decisions: &[Decision]) -> String {
+    decisions.iter().map(|(a, b)| format!(" {}-{}", a, b)).collect()
+}
+
+/// The rest of the next line, which has to start with the key
+fn record(lines: &mut impl Iterator<Item = String>, key: &str) -> Result<String, String> {
+    match lines.next() {
+        Some(ref line) if line.split_whitespace().next() == Some(key) => Ok(line[key.len()..].to_owned()),
+        Some(line) => Err(format!("Expected {}, found: {}", key, line)),
+        None => Err(format!("Expected {}, the checkpoint ended", key))
+    }
+}
+
+fn parse_numbers<T: std::str::FromStr>(values: &str) -> Result<Vec<T>, String> {
+    values.split_whitespace()
+        .map(|value| value.parse::<T>().map_err(|_| format!("Not a number: {}", value)))
+        .collect()
+}
+
+fn parse_decisions(values: &str) -> Result<Vec<Decision>, String> {
+    values.split_whitespace()
+        .map(|value| {
+            let mut ids = value.splitn(2, '-').map(|id| id.parse::<usize>());
+            match (ids.next(), ids.next()) {
+                (Some(Ok(a)), Some(Ok(b))) => Ok((a, b)),
+                _ => Err(format!("Not a disjunction: {}", value))
+            }
+        })
+        .collect()
+}
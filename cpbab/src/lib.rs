@@ -5,123 +5,522 @@ extern crate disjunctgraph;
 mod node;
 mod task_interval;
 mod propagation;
+mod restart;
+mod nogood;
+mod shaving;
+mod checkpoint;
+mod tree;
+mod replay;
 
 use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::{ Arc, Mutex };
+use std::sync::atomic::{ AtomicBool, Ordering };
 
 use disjunctgraph::{ GraphNode, ConstrainedNode, NodeId, Graph };
+use disjunctgraph::transposition::TranspositionTable;
 use itertools::Itertools;
+use rand::{ Rng, SeedableRng };
+use rand::rngs::SmallRng;
+
+pub use restart::Restarts;
+pub use shaving::Shaving;
+pub use node::Node;
+pub use checkpoint::{ Checkpoint, Checkpointing, GraphState, OpenNode };
+pub use tree::{ NodeOutcome, SearchTree, TreeNode };
+pub use replay::{ format_path, parse_path, replay, ReplayFailure, Step };
 
 // Constrained graph ;
-pub type CGraph = disjunctgraph::LinkedGraph<node::Node>;
+pub type CGraph = disjunctgraph::LinkedGraph<Node>;
 
-type TaskInterval<'a> = task_interval::TaskInterval<'a, CGraph>;
+type TaskInterval<'a, G> = task_interval::TaskInterval<'a, G>;
+type ResourceIntervals = task_interval::ResourceIntervals;
+/// Orderings to branch on, in the order they are explored.
+type Branches<'a, N> = Vec<(&'a N, &'a N)>;
 
 const PAR: u32 = 3;
 
+/// Options for the branch and bound search
+#[derive(Clone, Debug, Default)]
+pub struct SearchConfig {
+    /// Seed for random tie-breaking in the branching, without a seed ties are broken deterministically.
+    pub seed: Option<u64>,
+    /// Restart the search from the root on this schedule, the incumbent and upper bound are kept.
+    pub restarts: Option<Restarts>,
+    /// Maximum number of nogoods kept, 0 disables nogood recording.
+    pub nogoods: usize,
+    /// Number of slots in the transposition table, 0 disables it.
+    pub transpositions: usize,
+    /// Shaving of heads and tails as stronger propagation.
+    pub shaving: Shaving,
+    /// Fix disjunctions whose order is implied by a path of precedences, so propagation sees them as arcs.
+    pub implied: bool,
+    /// The search returns the best schedule so far once this is set, from another thread.
+    pub stop: Option<Arc<AtomicBool>>,
+    /// Periodically write the state of the search to a file, it is continued with `resume`.
+    pub checkpoint: Option<Checkpointing>,
+    /// Record every node of the search in this tree, it is locked for every recorded node.
+    pub record: Option<Arc<Mutex<SearchTree>>>,
+}
+
+/// Counters of a search
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// Node evaluations, including those before a resume
+    pub nodes: usize,
+    /// Runs of the search, every restart starts a new run
+    pub runs: usize,
+    pub solutions: usize,
+    /// Fixations that were propagated, by branching and to explain failures
+    pub propagations: usize,
+    /// Nogoods in the store when the search ended
+    pub nogoods: usize,
+    pub transpositions: usize,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Node evaluations: {}, runs: {}, solutions: {}, propagations: {}, nogoods: {}, transpositions: {}",
+            self.nodes, self.runs, self.solutions, self.propagations, self.nogoods, self.transpositions)
+    }
+}
+
+/// The best schedule found, or the root when there is no schedule within the makespan.
+#[derive(Clone, Debug)]
+pub struct Solution<G> {
+    pub graph: G,
+    /// None when no schedule was found
+    pub makespan: Option<u32>,
+    pub stats: Stats,
+}
+
+impl<G> Solution<G> {
+    /// The schedule of a feasible outcome, otherwise the root
+    fn new(outcome: Outcome<G>, root: G, stats: Stats) -> Self {
+        match outcome {
+            Outcome::Feasible(graph, makespan) => Solution { graph, makespan: Some(makespan), stats },
+            _ => Solution { graph: root, makespan: None, stats }
+        }
+    }
+}
+
+struct SearchNode<G> {
+    graph: G,
+    /// Disjunctions fixed by branching, from the root to this node. They identify the node, see `replay`.
+    decisions: Vec<nogood::Decision>,
+    /// Task intervals of every resource, as they were in the parent. Synced when the node is expanded.
+    intervals: Vec<ResourceIntervals>,
+    /// Id in the recorded search tree
+    recorded: Option<usize>,
+}
+
+/// State of an interrupted search, restored from a checkpoint
+struct Resumed<G> {
+    upper_bound: u32,
+    best: Option<(G, u32)>,
+    nogoods: Vec<Vec<nogood::Decision>>,
+    node_evaluations: usize,
+    runs: usize,
+    run_evaluations: usize,
+    open: VecDeque<SearchNode<G>>,
+}
+
 // What is still needed?
 // There is no propagation of constraints,
 // Operations that have no disjunctions left are looked at too.
 // I believe cycles can occur? ()
-pub fn branch_and_bound(mut root: CGraph, resources: usize, max_makespan: u32) -> CGraph {
+/// Search for a schedule with a makespan of at most `max_makespan`, `resources` are numbered from 1.
+/// Any graph can be searched as long as its nodes carry heads and tails.
+pub fn branch_and_bound<G: Graph + Clone + Debug>(root: G, resources: usize, max_makespan: u32) -> Result<Solution<G>, String> where G::Node: ConstrainedNode + Debug {
+    branch_and_bound_with(root, resources, max_makespan, &SearchConfig::default())
+}
+
+/// The root is returned when there is no schedule within `max_makespan`.
+/// Errors when the root is infeasible or a checkpoint could not be written.
+pub fn branch_and_bound_with<G: Graph + Clone + Debug>(mut root: G, resources: usize, max_makespan: u32, config: &SearchConfig) -> Result<Solution<G>, String> where G::Node: ConstrainedNode + Debug {
     root.init_weights();//.expect("Problem with makespan is not feasible");    
-    dbg!(crate::propagation::search_orders(&mut root, max_makespan).unwrap());
+    tighten_root(&mut root, max_makespan, config).map_err(|e| format!("Root is infeasible: {}", e))?;
+
+    let resources = (1..=resources).collect::<Vec<_>>();
+    let (outcome, stats) = search(root.clone(), &resources, max_makespan, config, usize::MAX, false, None)?;
+    Ok(Solution::new(outcome, root, stats))
+}
+
+/// Continue a search of `branch_and_bound_with` from a checkpoint, `root` is the graph that search was
+/// started with, and `config` the configuration it had. The open nodes are explored in the order they had
+/// and random tie-breaking continues as it would have, so the search explores the same tree.
+/// Only transposition hits can differ: the table is not stored, states visited before the interruption are searched again.
+pub fn resume<G: Graph + Clone + Debug>(root: G, resources: usize, checkpoint: &Checkpoint, config: &SearchConfig) -> Result<Solution<G>, String> where G::Node: ConstrainedNode + Debug {
+    let tightened = checkpoint.root.restore(&root)?;
+    let best = match &checkpoint.best {
+        Some((best, length)) => Some((best.restore(&root)?, *length)),
+        None => None
+    };
 
     let resources = (1..=resources).collect::<Vec<_>>();
-    for resource in &resources {
-        crate::propagation::edge_finding(*resource as u32, &mut root, max_makespan).unwrap();
+    let mut open = VecDeque::new();
+    for node in &checkpoint.open {
+        let graph = node.graph.restore(&root)?;
+        // The node may have been created under a larger upper bound, the intervals are synced with the current one when it is expanded.
+        let intervals = resources.iter().map(|r| ResourceIntervals::new(*r as u32, &graph, checkpoint.max_makespan)).collect::<Result<_, _>>()?;
+        open.push_back(SearchNode { graph, decisions: node.decisions.clone(), intervals, recorded: None });
     }
-    //root.init_weights();
-    //root.search_orders(max_makespan);
+
+    let resumed = Resumed {
+        upper_bound: checkpoint.upper_bound,
+        best,
+        nogoods: checkpoint.nogoods.clone(),
+        node_evaluations: checkpoint.node_evaluations,
+        runs: checkpoint.runs,
+        run_evaluations: checkpoint.run_evaluations,
+        open
+    };
+    let (outcome, stats) = search(tightened.clone(), &resources, checkpoint.max_makespan, config, usize::MAX, false, Some(resumed))?;
+    Ok(Solution::new(outcome, tightened, stats))
+}
+
+/// Result of the decision version of the search
+#[derive(Clone, Debug)]
+pub enum Outcome<G> {
+    /// A schedule and its makespan, which is at most the asked makespan
+    Feasible(G, u32),
+    /// There is no schedule with at most the asked makespan
+    Infeasible,
+    /// The budget ran out before either was shown
+    Unknown,
+}
+
+/// Decision version of the search: is there a schedule with a makespan of at most `makespan`?
+/// Stops at the first schedule found or after `budget` node evaluations.
+/// No checkpoints are written, a decision search can not be resumed.
+pub fn decide<G: Graph + Clone + Debug>(mut root: G, resources: usize, makespan: u32, budget: usize, config: &SearchConfig) -> (Outcome<G>, Stats) where G::Node: ConstrainedNode + Debug {
     root.init_weights();
-    
-    //println!("{:?}", root);
-    let mut upper_bound = max_makespan;
-    let mut current_best = root.clone();
+    if tighten_root(&mut root, makespan, config).is_err() {
+        return (Outcome::Infeasible, Stats::default());
+    }
+
+    let resources = (1..=resources).collect::<Vec<_>>();
+    let config = SearchConfig { checkpoint: None, ..config.clone() };
+    match search(root, &resources, makespan, &config, budget, true, None) {
+        Ok(outcome) => outcome,
+        Err(e) => unreachable!("Search without checkpoints failed: {}", e)
+    }
+}
 
-    let mut stack: VecDeque<CGraph> = VecDeque::new();
-    stack.push_front(root);
+/// Search below a propagated root, for the best schedule or only the first when `first` is set.
+/// Feasible holds the best schedule found, even when the budget ran out. Errors when a checkpoint could not be written.
+fn search<G: Graph + Clone + Debug>(mut root: G, resources: &[usize], max_makespan: u32, config: &SearchConfig, budget: usize, first: bool, mut resumed: Option<Resumed<G>>) -> Result<(Outcome<G>, Stats), String> where G::Node: ConstrainedNode + Debug {
+    let mut upper_bound = max_makespan;
+    let mut best: Option<(G, u32)> = None;
+    let mut exhausted = false;
 
+    let mut schedule = config.restarts.map(restart::RestartSchedule::new);
+    let mut nogoods = nogood::NogoodStore::new(config.nogoods);
+    let mut transpositions = TranspositionTable::new(config.transpositions);
+    let recorder = tree::Recorder(config.record.as_deref());
     
     let mut node_evaluations = 0;
-    while let Some(node) = stack.pop_front() {        
-        node_evaluations += 1;
-
-        // Check if graph has disjunctions left.
-        if !node.has_disjunctions() {
-            // We are a complete schedule!
-            
-            let length = dbg!(node.critical_length().expect("Could not calculate critical length"));
-            let lb = lower_bound(&node, upper_bound, &resources);
-            if length <= upper_bound {
-                upper_bound = length;
-                current_best = node;
+    let mut runs = 0;
+    let mut solutions = 0;
+    let mut propagations = 0;
+    if let Some(resumed) = &mut resumed {
+        upper_bound = resumed.upper_bound;
+        best = resumed.best.take();
+        // Nogoods are stored newest first
+        resumed.nogoods.drain(..).rev().for_each(|nogood| nogoods.add(nogood));
+        node_evaluations = resumed.node_evaluations;
+        // The interrupted run is continued with the limit it had
+        runs = resumed.runs.saturating_sub(1);
+        if let Some(schedule) = schedule.as_mut() {
+            (0..runs).for_each(|_| { schedule.next_limit(); });
+        }
+    }
+    let mut last_checkpoint = node_evaluations;
+    let mut rng = generator(config, node_evaluations);
+
+    loop {
+        runs += 1;
+        let limit = schedule.as_mut().map_or(usize::MAX, |s| s.next_limit());
+
+        let (mut stack, mut run_evaluations) = match resumed.take() {
+            Some(mut resumed) => {
+                // The ancestors of resumed nodes are not known, they are recorded as roots.
+                for node in resumed.open.iter_mut() {
+                    node.recorded = recorder.add(|| TreeNode {
+                        parent: None, run: runs, upper_bound, decision: node.decisions.last().cloned(),
+                        lower_bound: Some(lower_bound(&node.graph, upper_bound, resources)),
+                        slack: Some(total_slack(&node.graph, upper_bound)),
+                        outcome: NodeOutcome::Open
+                    });
+                }
+                (resumed.open, resumed.run_evaluations)
+            },
+            None => {
+                // If the intervals of the root are infeasible then the incumbent is optimal.
+                let intervals = match resources.iter().map(|r| ResourceIntervals::new(*r as u32, &root, upper_bound)).collect() {
+                    Ok(intervals) => intervals,
+                    Err(_) => {
+                        exhausted = true;
+                        break;
+                    }
+                };
+
+                let recorded = recorder.add(|| TreeNode {
+                    parent: None, run: runs, upper_bound, decision: None,
+                    lower_bound: Some(lower_bound(&root, upper_bound, resources)),
+                    slack: Some(total_slack(&root, upper_bound)),
+                    outcome: NodeOutcome::Open
+                });
+                let mut stack: VecDeque<SearchNode<G>> = VecDeque::new();
+                stack.push_front(SearchNode { graph: root.clone(), decisions: Vec::new(), intervals, recorded });
+                (stack, 0)
             }
+        };
 
-            println!("We got one of length: {} or is it {}?", length, lb);
-        } else {
-            if lower_bound(&node, upper_bound, &resources) > upper_bound {                
+        while let Some(mut node) = stack.pop_front() {        
+            if run_evaluations >= limit || node_evaluations >= budget || stopped(config) {
+                stack.push_front(node);
+                break;
+            }
+
+            if let Some(checkpointing) = &config.checkpoint {
+                if checkpointing.interval > 0 && node_evaluations >= last_checkpoint + checkpointing.interval {
+                    let checkpoint = Checkpoint {
+                        max_makespan, upper_bound, node_evaluations, runs, run_evaluations,
+                        root: GraphState::new(&root),
+                        best: best.as_ref().map(|(graph, length)| (GraphState::new(graph), *length)),
+                        nogoods: nogoods.iter().cloned().collect(),
+                        open: std::iter::once(&node).chain(&stack)
+                            .map(|n| OpenNode { decisions: n.decisions.clone(), graph: GraphState::new(&n.graph) })
+                            .collect(),
+                    };
+                    checkpoint.save(&checkpointing.path)
+                        .map_err(|e| format!("Could not write checkpoint to {}: {}", checkpointing.path.display(), e))?;
+                    last_checkpoint = node_evaluations;
+                    // A search resumed from this checkpoint continues with the same generator.
+                    rng = generator(config, node_evaluations);
+                }
+            }
+            run_evaluations += 1;
+            node_evaluations += 1;
+
+            if nogoods.violated(&node.graph) {
+                recorder.close(node.recorded, NodeOutcome::Nogood);
                 continue;
             }
-            //println!("Disjunctions left: {}", node.total_disjunctions());
-            if let Ok(pairs) = next_pair(&resources, &node, upper_bound) {
-                for (t1, t2) in pairs {
-                    if t1.head() + t1.weight() + t2.weight() + t2.tail() > upper_bound {
+
+            // The same selection can be reached by fixing the disjunctions in a different order.
+            if transpositions.visit(node.graph.selection_hash(), upper_bound) {
+                recorder.close(node.recorded, NodeOutcome::Transposition);
+                continue;
+            }
+
+            // Check if graph has disjunctions left.
+            if !node.graph.has_disjunctions() {
+                // We are a complete schedule!
+                
+                let length = match node.graph.critical_length() {
+                    Ok(length) => length,
+                    Err(_) => {
+                        recorder.close(node.recorded, NodeOutcome::Infeasible);
                         continue;
                     }
-                    debug_assert!(t1.head() + t1.weight() + t2.weight() + t2.tail() <= upper_bound);
-                    let mut graph = node.clone();
-
-
-                    println!("Total slack before: {}", graph.nodes().iter().map(|n| {
-                        upper_bound - n.head() - n.weight() - n.tail()
-                    }).sum::<u32>());
-
-                    graph.fix_disjunction(t1, t2).expect("Could not fix disjunction");
-                    
-                    let result = propagation::propagate_fixation(&mut graph, t1, t2, upper_bound);
-                    match result {
-                        Err(_) => (),//println!("Error: {}", e),
-                        Ok(_) => {
-
-                            println!("Total slack after: {}", graph.nodes().iter().map(|n| {
-                                upper_bound - n.head() - n.weight() - n.tail()
-                            }).sum::<u32>());
-                            if lower_bound(&graph, max_makespan, &resources) <= upper_bound {
-                                stack.push_front(graph);                            
+                };
+
+                if length <= upper_bound {
+                    recorder.close(node.recorded, NodeOutcome::Solution(length));
+                    solutions += 1;
+                    upper_bound = length;
+                    best = Some((node.graph, length));
+                    if first {
+                        break;
+                    }
+                } else {
+                    recorder.close(node.recorded, NodeOutcome::PrunedByBound);
+                }
+            } else {
+                if lower_bound(&node.graph, upper_bound, resources) > upper_bound {                
+                    recorder.close(node.recorded, NodeOutcome::PrunedByBound);
+                    continue;
+                }
+                //println!("Disjunctions left: {}", node.total_disjunctions());
+                // Only the intervals touched by the changes since the parent are updated.
+                let parent = &node.graph;
+                if node.intervals.iter_mut().try_for_each(|i| i.sync(parent, upper_bound)).is_err() {
+                    recorder.close(node.recorded, NodeOutcome::Infeasible);
+                    continue;
+                }
+
+                let pairs = next_pair(&node.intervals, &node.graph, upper_bound, &mut rng);
+                recorder.close(node.recorded, if pairs.is_ok() { NodeOutcome::Branched } else { NodeOutcome::Infeasible });
+                if let Ok(pairs) = pairs {
+                    for (t1, t2) in pairs {
+                        let bound = t1.head() + t1.weight() + t2.weight() + t2.tail();
+                        if bound > upper_bound {
+                            recorder.add(|| TreeNode {
+                                parent: node.recorded, run: runs, upper_bound, decision: Some((t1.id(), t2.id())),
+                                lower_bound: Some(bound), slack: None,
+                                outcome: NodeOutcome::PrunedByBound
+                            });
+                            continue;
+                        }
+                        debug_assert!(bound <= upper_bound, "Branch {} -> {} exceeds the upper bound at node: {}", t1.id(), t2.id(), path(&node.decisions));
+                        let mut graph = node.graph.clone();
+                        let mut decisions = node.decisions.clone();
+                        decisions.push((t1.id(), t2.id()));
+
+                        // The propagation of a fixation that completes a nogood fails, it is skipped.
+                        if nogoods.completed(&node.graph, (t1.id(), t2.id())) {
+                            recorder.add(|| TreeNode {
+                                parent: node.recorded, run: runs, upper_bound, decision: Some((t1.id(), t2.id())),
+                                lower_bound: None, slack: None,
+                                outcome: NodeOutcome::Nogood
+                            });
+                            continue;
+                        }
+
+                        // Fixing fails when t2 already precedes t1
+                        propagations += 1;
+                        let result = replay::apply(&mut graph, Step::Fix(t1.id(), t2.id()), upper_bound, config);
+                        match result {
+                            Err(_) => {
+                                recorder.add(|| TreeNode {
+                                    parent: node.recorded, run: runs, upper_bound, decision: Some((t1.id(), t2.id())),
+                                    lower_bound: None, slack: None,
+                                    outcome: NodeOutcome::Infeasible
+                                });
+                                if nogoods.enabled() {
+                                    let (nogood, replayed) = nogood::explain(&root, &decisions, upper_bound);
+                                    propagations += replayed;
+                                    nogoods.add(nogood);
+                                }
+                            },
+                            Ok(_) => {
+                                let bound = lower_bound(&graph, max_makespan, resources);
+                                let recorded = recorder.add(|| TreeNode {
+                                    parent: node.recorded, run: runs, upper_bound, decision: Some((t1.id(), t2.id())),
+                                    lower_bound: Some(bound), slack: Some(total_slack(&graph, upper_bound)),
+                                    outcome: if bound <= upper_bound { NodeOutcome::Open } else { NodeOutcome::PrunedByBound }
+                                });
+                                if bound <= upper_bound {
+                                    stack.push_front(SearchNode { graph, decisions, intervals: node.intervals.clone(), recorded });
+                                }
                             }
                         }
                     }
                 }
             }
         }
+
+        // The stack is exhausted, the whole tree has been searched.
+        if stack.is_empty() {
+            exhausted = true;
+            break;
+        }
+
+        if node_evaluations >= budget || (first && best.is_some()) || stopped(config) {
+            break;
+        }
+
+        // Restart, the root is tightened with the improved upper bound.
+        // If that is infeasible then the incumbent is optimal.
+        // Visited states of the abandoned run were not completely searched.
+        transpositions.clear();
+        if tighten_root(&mut root, upper_bound, config).is_err() {
+            exhausted = true;
+            break;
+        }
+    }
+
+    let outcome = match best {
+        Some((graph, length)) => Outcome::Feasible(graph, length),
+        None if exhausted => Outcome::Infeasible,
+        None => Outcome::Unknown
+    };
+    let stats = Stats { nodes: node_evaluations, runs, solutions, propagations, nogoods: nogoods.len(), transpositions: transpositions.hits() };
+    Ok((outcome, stats))
+}
+
+fn fix_implied<G: Graph>(graph: &mut G, config: &SearchConfig) -> Result<(), String> {
+    if config.implied {
+        disjunctgraph::remove_implied(graph).map_err(|e| format!("Could not fix implied disjunctions, {:?}", e))?;
+    }
+    Ok(())
+}
+
+/// Sum of the slack of every node under the upper bound
+fn total_slack<G: Graph>(graph: &G, upper_bound: u32) -> u32 where G::Node: ConstrainedNode {
+    graph.nodes().iter().map(|n| upper_bound.saturating_sub(n.head() + n.weight() + n.tail())).sum()
+}
+
+/// Generator for random tie-breaking from `node_evaluations` on. The search is re-seeded at every checkpoint,
+/// because the state of a generator is not stored.
+fn generator(config: &SearchConfig, node_evaluations: usize) -> Option<SmallRng> {
+    config.seed.map(|seed| SmallRng::seed_from_u64(seed ^ (node_evaluations as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)))
+}
+
+fn stopped(config: &SearchConfig) -> bool {
+    matches!(&config.stop, Some(stop) if stop.load(Ordering::Relaxed))
+}
+
+/// Propagate a (new) upper bound on the root of the search.
+fn tighten_root<G: Graph + Clone + Debug>(root: &mut G, upper_bound: u32, config: &SearchConfig) -> Result<(), String> where G::Node: ConstrainedNode + Debug {
+    propagation::propagate(root, upper_bound)?;
+    if config.shaving != Shaving::Off {
+        shaving::shave(root, upper_bound)?;
+    }
+    Ok(())
+}
+
+/// Fix the decisions on a copy of the root and propagate every fixation, without shaving or implied disjunctions.
+/// Decisions that propagation already fixed in the same direction are skipped.
+fn apply_decisions<G: Graph + Clone + Debug>(root: &G, decisions: &[nogood::Decision], upper_bound: u32) -> Result<G, String> where G::Node: ConstrainedNode + Debug {
+    let mut graph = root.clone();
+    let config = SearchConfig::default();
+    for decision in decisions {
+        replay::apply(&mut graph, Step::from(*decision), upper_bound, &config)?;
+    }
+    Ok(graph)
+}
+
+fn path(decisions: &[nogood::Decision]) -> String {
+    format_path(&decisions.iter().map(|d| Step::from(*d)).collect_vec())
+}
+
+/// Select the element with the minimal key. Ties are broken at random when a generator is given,
+/// otherwise the first minimal element is returned.
+fn min_by_key_random<T, K: Ord>(items: impl IntoIterator<Item = T>, rng: &mut Option<SmallRng>, mut key: impl FnMut(&T) -> K) -> Option<T> {
+    match rng {
+        None => items.into_iter().min_by_key(key),
+        Some(rng) => {
+            let mut items = items.into_iter().map(|x| (key(&x), x)).collect_vec();
+            let min_key = items.iter().map(|(k, _)| k).min()?;
+            let ties = items.iter().filter(|(k, _)| k == min_key).count();
+            let chosen = rng.gen_range(0, ties);
+            let index = items.iter().positions(|(k, _)| k == min_key).nth(chosen)?;
+            Some(items.swap_remove(index).1)
+        }
     }
-    println!("Node evaluations: {}", node_evaluations);
-    current_best
 }
 
 
 
-fn next_pair<'a>(resources: &[usize], graph: &'a CGraph, upper_bound: u32) -> Result<Vec<(&'a node::Node, &'a node::Node)>, String> {
+fn next_pair<'a, G: Graph + Debug>(intervals: &[ResourceIntervals], graph: &'a G, upper_bound: u32, rng: &mut Option<SmallRng>) -> Result<Branches<'a, G::Node>, String> where G::Node: ConstrainedNode + Debug {
 
     // Calculate the critical task interval for each resource/machine
     // Returns true if machine still has operations that need to be ordered
-    let resource_filter = |id: &&usize| -> bool {
+    let resource_filter = |intervals: &&ResourceIntervals| -> bool {
         graph.nodes().iter()
-            .filter(|x| x.machine_id() == Some(**id as u32))
+            .filter(|x| x.machine_id() == Some(intervals.resource()))
             .any(|x| graph.node_has_disjunction(x))
     };
-    let criticals: Vec<(usize, TaskInterval)> = resources.iter()
-        .filter(resource_filter)        
-        .filter_map(|id|            
-            match crit(*id, graph, upper_bound) {
-                Ok(ti) => ti.map(|x| Ok((*id, x))),
-                Err(e) => Some(Err(e))
-            }            
-        ).collect::<Result<Vec<_>, String>>()?;
+    let criticals: Vec<(usize, TaskInterval<G>)> = intervals.iter()
+        .filter(resource_filter)
+        .filter_map(|intervals| crit(intervals, graph).map(|x| (intervals.resource() as usize, x)))
+        .collect();
     
     // Find the resource with the most constrained task interval
-    let (resource_id, crit) = criticals.into_iter()
-        .min_by_key(|(id, cr)| {
+    let (resource_id, crit) = min_by_key_random(criticals, rng, |(id, cr)| {
             let resource_slack = resource_slack(*id as u32, graph, upper_bound);
             cr.slack() as u32 * resource_slack * std::cmp::min(PAR, num_choices(cr) as u32)
         })
@@ -136,12 +535,12 @@ fn next_pair<'a>(resources: &[usize], graph: &'a CGraph, upper_bound: u32) -> Re
     let resource_nodes = graph.nodes().iter().filter(|x| x.machine_id() == Some(resource_id as u32)).collect_vec();
     let crit_slack = crit.slack();
 
-    let can_be_first = |t: &&&node::Node| -> bool {
+    let can_be_first = |t: &&&G::Node| -> bool {
         t.head() <= t1.head() + crit_slack 
         && t.id() != t1.id()
         && graph.has_disjunction(&t1.id(), &t.id())        
     };
-    let can_be_last = |t: &&&node::Node| -> bool {
+    let can_be_last = |t: &&&G::Node| -> bool {
         t.tail() <= t2.tail() + crit_slack        
         && t.id() != t2.id()
         && graph.has_disjunction(&t.id(), &t2.id())        
@@ -165,11 +564,10 @@ fn next_pair<'a>(resources: &[usize], graph: &'a CGraph, upper_bound: u32) -> Re
             .filter(|x| x.id() != t1.id())
             .map(|x| x.head()).min().expect("No min S1 found") - t1.head();
 
-        let t = s1.iter()
-            .min_by_key(|t| left_bounded_entropy(t1, t, upper_bound, &crit, delta))
+        let t = min_by_key_random(s1, rng, |t| left_bounded_entropy::<G>(t1, t, upper_bound, &crit, delta))
             .expect("Could not minimize h1");
 
-        if g(t1, t, upper_bound) <= g(t, t1, upper_bound) {
+        if first_branch(g::<G::Node>(t1, t, upper_bound), g::<G::Node>(t, t1, upper_bound), rng) {
             Ok(vec!((t1, t), (t, t1)))
         } else {
             Ok(vec!((t, t1), (t1, t)))
@@ -178,11 +576,10 @@ fn next_pair<'a>(resources: &[usize], graph: &'a CGraph, upper_bound: u32) -> Re
         let delta = t2.lct(upper_bound) - crit.nodes.iter()
             .filter(|x| x.id() != t2.id())
             .map(|x| x.lct(upper_bound)).max().expect("No max S2 found");
-        let t = s2.iter()
-            .min_by_key(|t| right_bounded_entropy(t, t2, upper_bound, &crit, delta))
+        let t = min_by_key_random(s2, rng, |t| right_bounded_entropy::<G>(t, t2, upper_bound, &crit, delta))
             .expect("Could not minimize h2");
 
-        if g(t, t2, upper_bound) <= g(t2, t, upper_bound) {
+        if first_branch(g::<G::Node>(t, t2, upper_bound), g::<G::Node>(t2, t, upper_bound), rng) {
             Ok(vec!((t, t2), (t2, t)))
         } else {
             Ok(vec!((t2, t), (t, t2)))
@@ -191,23 +588,26 @@ fn next_pair<'a>(resources: &[usize], graph: &'a CGraph, upper_bound: u32) -> Re
 }
 
 
-/// Find the critical on a resource, if there is no found then there inconsistency
-/// It can happen that a resource is already completely scheduled.
-fn crit<'a>(resource_id: usize, graph: &'a CGraph, upper_bound: u32) -> Result<Option<TaskInterval<'a>>, String> {
-    
-    // Get the nodes on the resources
-    
-    let task_intervals = task_interval::find_task_intervals(resource_id as u32, graph, upper_bound)?;
-    
-    debug_assert!(task_intervals.len() > 0);
+/// Whether the branch with impact `a` is explored before the branch with impact `b`.
+/// Equal impacts are ordered at random when a generator is given.
+fn first_branch(a: u32, b: u32, rng: &mut Option<SmallRng>) -> bool {
+    match rng {
+        Some(rng) if a == b => rng.gen(),
+        _ => a <= b
+    }
+}
 
+/// Find the critical task interval on a resource, the intervals have to be synced with the graph.
+/// It can happen that a resource is already completely scheduled.
+fn crit<'a, G: Graph + Debug>(intervals: &ResourceIntervals, graph: &'a G) -> Option<TaskInterval<'a, G>> where G::Node: ConstrainedNode + Debug {
     // Only resources are considered that have more than 1 node anyway.
-    Ok(task_intervals.into_iter()        
-        .min_by_key(|x| x.slack() as u32 * num_choices(x) as u32))
+    intervals.intervals()
+        .min_by_key(|x| x.slack() * x.num_choices() as u32)
+        .map(|x| intervals.task_interval(x, graph))
 }
 
 /// Calculate the slack for all operations on a resource
-fn resource_slack(resource: u32, graph: &CGraph, upper_bound: u32) -> u32 {
+fn resource_slack<G: Graph>(resource: u32, graph: &G, upper_bound: u32) -> u32 where G::Node: ConstrainedNode {
     let (min, max, p) = graph.nodes().iter()
         .filter(|x| x.machine_id() == Some(resource as u32))
         .fold((std::u32::MAX, 0, 0), |(min, max, p), x| {
@@ -221,7 +621,7 @@ fn resource_slack(resource: u32, graph: &CGraph, upper_bound: u32) -> u32 {
 
 
 /// When fixing t1 -> t2, this function returns the expected reduction in entropy on the domain
-fn left_bounded_entropy(t1: &node::Node, tb: &node::Node, max_makespan: u32, task_interval: &TaskInterval, delta: u32) -> u32 {
+fn left_bounded_entropy<G: Graph + Debug>(t1: &G::Node, tb: &G::Node, max_makespan: u32, task_interval: &TaskInterval<G>, delta: u32) -> u32 where G::Node: ConstrainedNode + Debug {
     let t1_tb = g(t1, tb, max_makespan);
     let tb_t1 = g(tb, t1, max_makespan);
 
@@ -234,7 +634,7 @@ fn left_bounded_entropy(t1: &node::Node, tb: &node::Node, max_makespan: u32, tas
     std::cmp::max(t1_tb, std::cmp::min(tb_t1, fff))
 }
 
-fn right_bounded_entropy(ta: &node::Node, t2: &node::Node, upper_bound: u32, task_interval: &TaskInterval, delta: u32) -> u32 {
+fn right_bounded_entropy<G: Graph + Debug>(ta: &G::Node, t2: &G::Node, upper_bound: u32, task_interval: &TaskInterval<G>, delta: u32) -> u32 where G::Node: ConstrainedNode + Debug {
     let ta_t2 = g(ta, t2, upper_bound);
     let t2_ta = g(t2, ta, upper_bound);
 
@@ -248,7 +648,7 @@ fn right_bounded_entropy(ta: &node::Node, t2: &node::Node, upper_bound: u32, tas
 }
 
 /// Assess impact of an ordering ta -> tb
-fn g(ta: &node::Node, tb: &node::Node, upper_bound: u32) -> u32 {
+fn g<N: ConstrainedNode>(ta: &N, tb: &N, upper_bound: u32) -> u32 {
     let da = ta.lct(upper_bound).saturating_sub(tb.lst(upper_bound));
     let db = (ta.head() + ta.weight()).saturating_sub(tb.head());    
 
@@ -269,7 +669,7 @@ fn evaluation(slack: u32, delta: u32, max_makespan: u32) -> u32 {
 }
 
 
-fn num_choices(task_interval: &TaskInterval) -> usize {
+fn num_choices<G: Graph + Debug>(task_interval: &TaskInterval<G>) -> usize where G::Node: ConstrainedNode + Debug {
     std::cmp::min(task_interval.nc_start.len(),  task_interval.nc_end.len())
 }
 
@@ -277,7 +677,7 @@ fn num_choices(task_interval: &TaskInterval) -> usize {
 /// Chapter 4.4: Lower bound
 /// Warning: Does not implement all three bounds.
 /// A study of lower bounds is acceptable
-fn lower_bound(graph: &CGraph, upper_bound: u32, resources: &[usize]) -> u32 {    
+fn lower_bound<G: Graph>(graph: &G, upper_bound: u32, resources: &[usize]) -> u32 where G::Node: ConstrainedNode {    
     let resources = resources.iter()
         .map(|resource| graph.nodes().iter().filter(move |n| n.machine_id() == Some(*resource as u32))) // Returns an I_k on machine M_k
         .collect::<Vec<_>>();
@@ -1,4 +1,5 @@
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde_derive::Serialize, serde_derive::Deserialize))]
 pub struct Node {
     id: usize,
     job_id: Option<usize>,
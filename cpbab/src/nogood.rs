@@ -0,0 +1,91 @@
+//! Nogood recording: when propagation of a fixation fails, the decisions that led to it
+//! are recorded, reduced to a small set that still fails from the root when the failure is close to the root.
+//! Any node containing all precedences of a recorded nogood can not contain a solution and is skipped,
+//! as is a fixation that would complete a nogood, without propagating it.
+//!
+//! A nogood found under some upper bound stays valid when the upper bound decreases.
+use disjunctgraph::{ ConstrainedNode, Graph };
+use itertools::Itertools;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+
+/// A disjunction fixed by the search: node_1 -> node_2
+pub type Decision = (usize, usize);
+
+pub struct NogoodStore {
+    capacity: usize,
+    nogoods: VecDeque<Vec<Decision>>,
+}
+
+impl NogoodStore {
+    pub fn new(capacity: usize) -> Self {
+        NogoodStore { capacity, nogoods: VecDeque::with_capacity(capacity) }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.nogoods.len()
+    }
+
+    /// Nogoods from the newest to the oldest
+    pub fn iter(&self) -> impl Iterator<Item = &Vec<Decision>> {
+        self.nogoods.iter()
+    }
+
+    /// Store a nogood, the oldest nogood is dropped when the store is full.
+    pub fn add(&mut self, nogood: Vec<Decision>) {
+        if !self.enabled() {
+            return;
+        }
+        if self.nogoods.len() >= self.capacity {
+            self.nogoods.pop_back();
+        }
+        self.nogoods.push_front(nogood);
+    }
+
+    /// The graph contains every precedence of some recorded nogood.
+    pub fn violated<G: Graph>(&self, graph: &G) -> bool {
+        self.nogoods.iter().any(|nogood| nogood.iter().all(|(a, b)| graph.has_precedence(a, b)))
+    }
+
+    /// Fixing the decision in the graph completes a recorded nogood, so its propagation would fail.
+    pub fn completed<G: Graph>(&self, graph: &G, decision: Decision) -> bool {
+        self.nogoods.iter()
+            .filter(|nogood| nogood.contains(&decision))
+            .any(|nogood| nogood.iter().all(|(a, b)| (*a, *b) == decision || graph.has_precedence(a, b)))
+    }
+}
+
+/// Failures deeper than this are not explained, the decisions themselves are recorded.
+/// Explaining replays the decisions once for every decision, deeper it costs more propagation than the nogoods save.
+const EXPLAIN_DEPTH: usize = 2;
+
+/// Reduce failing decisions to a subset that still fails when applied on the root.
+/// Every decision is dropped once, if the rest still fails it is not needed for the failure.
+/// Only failures within `EXPLAIN_DEPTH` decisions are reduced. Returns the nogood and the number of replayed fixations.
+pub fn explain<G: Graph + Clone + Debug>(root: &G, decisions: &[Decision], upper_bound: u32) -> (Vec<Decision>, usize) where G::Node: ConstrainedNode + Debug {
+    let mut nogood = decisions.to_vec();
+    let mut replayed = 0;
+    if decisions.len() > EXPLAIN_DEPTH {
+        return (nogood, replayed);
+    }
+
+    let mut i = 0;
+    while i < nogood.len() {
+        let candidate = nogood.iter().enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, d)| *d)
+            .collect_vec();
+
+        replayed += candidate.len();
+        if crate::apply_decisions(root, &candidate, upper_bound).is_err() {
+            nogood = candidate;
+        } else {
+            i += 1;
+        }
+    }
+    (nogood, replayed)
+}
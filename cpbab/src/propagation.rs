@@ -35,8 +35,6 @@ use disjunctgraph::{ Graph, ConstrainedNode, GraphNode, NodeId };
 use itertools::Itertools;
 use std::collections::HashSet;
 
-use crate::task_interval;
-
 
 pub fn propagate_tail<I: Graph>(node: &impl NodeId, graph: &mut I, upper_bound: u32) -> Result<HashSet<usize>, String> where I::Node: ConstrainedNode {
     use std::collections::VecDeque;
@@ -99,42 +97,6 @@ pub fn propagate_head<I: Graph>(node: &impl NodeId, graph: &mut I, upper_bound:
     Ok(changed)
 }
 
-pub fn edge_finding<I: Graph + std::fmt::Debug>(resource: u32, graph: &mut I, upper_bound: u32) -> Result<(), String> where I::Node: ConstrainedNode + std::fmt::Debug {
-
-    let tis = task_interval::find_task_intervals(resource, graph, upper_bound)?;
-    
-    unimplemented!("Adjust nodes that cannot be last or first");
-
-    let starts = tis.iter()
-        .filter(|ti| ti.nc_start.len() == 1)
-        .flat_map(|ti| {
-            let id = ti.nc_start[0].id();
-            ti.nodes.iter()
-                .filter(|n| graph.has_disjunction(&n.id(), &id))
-                .map(|n| (id, n.id()))
-                .collect_vec()
-        });
-
-    let ends = tis.iter()
-        .filter(|ti| ti.nc_end.len() == 1)
-        .flat_map(|ti| {
-            let id = ti.nc_end[0].id();
-            ti.nodes.iter()
-                .filter(|n| graph.has_disjunction(&n.id(), &id))
-                .map(|n| (n.id(), id))
-                .collect_vec()
-        });
-
-    let fixations = starts.chain(ends).unique().collect_vec();
-
-    for (other, end) in fixations {
-        graph.fix_disjunction(&other, &end).or_else(|e| Err(format!("Could not fix disjunction {} -> {}, {:?}", other, end, e)))?;
-        adjust_head_tail(graph, &other, &end, upper_bound)?;
-    }
-
-    Ok(())
-}
-
 fn adjust_head_tail<I: Graph>(graph: &mut I, node_1: &impl NodeId, node_2: &impl NodeId, upper_bound: u32) -> Result<(), String> where I::Node: ConstrainedNode {
     let node_1 = node_1.id();
     let node_2 = node_2.id();
@@ -164,11 +126,7 @@ pub fn propagate_fixation<I: Graph + std::fmt::Debug>(graph: &mut I, node_1: &im
     
     adjust_head_tail(graph, node_1, node_2, upper_bound)?;
 
-    while search_orders(graph, upper_bound)? {
-        for resource in 1..=10 {
-            edge_finding(resource, graph, upper_bound)?;            
-        }
-    }
+    propagate(graph, upper_bound)?;
 
     debug_assert!(graph.nodes().iter().all(|node|{
             let current_head = node.head();
@@ -186,6 +144,43 @@ pub fn propagate_fixation<I: Graph + std::fmt::Debug>(graph: &mut I, node_1: &im
 }
 
 
+/// Run the ordering rules and the resource propagation until nothing changes.
+pub fn propagate<I: Graph + std::fmt::Debug>(graph: &mut I, upper_bound: u32) -> Result<(), String> where I::Node: ConstrainedNode {
+    loop {
+        let ordered = search_orders(graph, upper_bound)?;
+        let propagated = propagate_resources(graph, upper_bound)?;
+        if !ordered && !propagated {
+            return Ok(());
+        }
+    }
+}
+
+/// Apply the detectable precedences, immediate selections and energetic reasoning found on all resources.
+/// Returns whether something changed.
+pub fn propagate_resources<I: Graph>(graph: &mut I, upper_bound: u32) -> Result<bool, String> where I::Node: ConstrainedNode {
+    let resources = graph.nodes().iter().filter_map(|n| n.machine_id()).unique().collect_vec();
+    let mut deductions = disjunctgraph::find_orders(&resources, graph, upper_bound)
+        .or(Err(format!("Resources can not be scheduled within {}", upper_bound)))?;
+    deductions.extend(disjunctgraph::energetic_reasoning(&resources, graph, upper_bound)
+        .or(Err(format!("Not enough energy on the resources to schedule within {}", upper_bound)))?);
+
+    let mut change_occured = disjunctgraph::apply_windows(&deductions, graph, upper_bound)
+        .or(Err(format!("Adjusted heads and tails do not fit within {}", upper_bound)))?;
+
+    for (node_1, node_2) in deductions.orders {
+        if graph.has_precedence(&node_2, &node_1) {
+            return Err(format!("Order {} -> {} contradicts {} -> {}", node_1, node_2, node_2, node_1));
+        }
+        if graph.has_disjunction(&node_1, &node_2) {
+            graph.fix_disjunction(&node_1, &node_2).or(Err(format!("Could not fix disjunction {} -> {}", node_1, node_2)))?;
+            adjust_head_tail(graph, &node_1, &node_2, upper_bound)?;
+            change_occured = true;
+        }
+    }
+
+    Ok(change_occured)
+}
+
 pub fn search_orders<T: Graph + std::fmt::Debug> (graph: &mut T, upper_bound: u32) -> Result<bool, String> where T::Node: ConstrainedNode {
     
     let mut change_occured = false;
@@ -0,0 +1,163 @@
+//! Replay of the decisions that lead to a node of the search, to reproduce its state outside of the search.
+//!
+//! A node is identified by its path, the decisions from the root: `1-5 3-7` fixes 1 -> 5 and then 3 -> 7.
+//! Time windows are restricted with `h4=10`, the head of 4 is at least 10, and `t4=2`, the tail of 4 is at least 2.
+use std::fmt::{ self, Debug, Display };
+use std::str::FromStr;
+
+use disjunctgraph::{ ConstrainedNode, Graph, GraphNode };
+
+use crate::nogood::Decision;
+use crate::{ propagation, shaving, SearchConfig, Shaving };
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Fix the disjunction node_1 -> node_2
+    Fix(usize, usize),
+    /// Raise the head of a node
+    Head(usize, u32),
+    /// Raise the tail of a node
+    Tail(usize, u32),
+}
+
+/// The first step of a replay that failed
+#[derive(Clone, Debug)]
+pub struct ReplayFailure<G> {
+    /// Index of the failing step, none when the root itself is infeasible
+    pub step: Option<usize>,
+    pub reason: String,
+    /// The graph before the failing step
+    pub graph: G,
+}
+
+/// Replay the steps on a fresh root with the propagation of the search: the root is propagated under the upper bound
+/// and every step is propagated as the search does with a fixation. Fixations that propagation already made are skipped.
+///
+/// The search propagates a node under the upper bound it had when the node was created, use that bound to reproduce the node.
+/// A search that improved its upper bound while descending propagated the ancestors under larger bounds,
+/// the replayed graph can then be tighter than the node was.
+pub fn replay<G: Graph + Clone + Debug>(mut root: G, steps: &[Step], upper_bound: u32, config: &SearchConfig) -> Result<G, ReplayFailure<G>> where G::Node: ConstrainedNode + Debug {
+    root.init_weights();
+    if let Err(reason) = crate::tighten_root(&mut root, upper_bound, config) {
+        return Err(ReplayFailure { step: None, reason, graph: root });
+    }
+
+    for (i, step) in steps.iter().enumerate() {
+        let mut graph = root.clone();
+        if let Err(reason) = apply(&mut graph, *step, upper_bound, config) {
+            return Err(ReplayFailure { step: Some(i), reason, graph: root });
+        }
+        root = graph;
+    }
+    Ok(root)
+}
+
+/// Apply a single step and propagate it.
+pub(crate) fn apply<G: Graph + Clone + Debug>(graph: &mut G, step: Step, upper_bound: u32, config: &SearchConfig) -> Result<(), String> where G::Node: ConstrainedNode + Debug {
+    match step {
+        Step::Fix(node_1, node_2) => {
+            if node_1 >= graph.nodes().len() || node_2 >= graph.nodes().len() {
+                return Err(format!("Decision {} -> {} is not in the graph", node_1, node_2));
+            }
+            if graph.has_precedence(&node_2, &node_1) {
+                return Err(format!("Decision {} -> {} contradicts fixed {} -> {}", node_1, node_2, node_2, node_1));
+            }
+            if !graph.has_disjunction(&node_1, &node_2) {
+                return Ok(());
+            }
+            graph.fix_disjunction(&node_1, &node_2).map_err(|e| format!("Could not fix disjunction {} -> {}, {:?}", node_1, node_2, e))?;
+            propagation::propagate_fixation(graph, &node_1, &node_2, upper_bound)?;
+        },
+        Step::Head(node, _) | Step::Tail(node, _) if node >= graph.nodes().len() => {
+            return Err(format!("Node {} is not in the graph", node));
+        },
+        Step::Head(node, head) => {
+            if head <= graph[node].head() {
+                return Ok(());
+            }
+            if head + graph[node].weight() + graph[node].tail() > upper_bound {
+                return Err(format!("Head {} of {} exceeds the upper bound", head, node));
+            }
+            graph[node].set_head(head);
+            propagation::propagate_head(&node, graph, upper_bound)?;
+            propagation::propagate(graph, upper_bound)?;
+        },
+        Step::Tail(node, tail) => {
+            if tail <= graph[node].tail() {
+                return Ok(());
+            }
+            if graph[node].head() + graph[node].weight() + tail > upper_bound {
+                return Err(format!("Tail {} of {} exceeds the upper bound", tail, node));
+            }
+            graph[node].set_tail(tail);
+            propagation::propagate_tail(&node, graph, upper_bound)?;
+            propagation::propagate(graph, upper_bound)?;
+        }
+    }
+
+    if config.shaving == Shaving::Nodes {
+        shaving::shave(graph, upper_bound)?;
+    }
+    crate::fix_implied(graph, config)
+}
+
+/// The path of a node as it is printed by the search, it is read back with `parse_path`.
+pub fn format_path(steps: &[Step]) -> String {
+    steps.iter().map(|step| step.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+pub fn parse_path(path: &str) -> Result<Vec<Step>, String> {
+    path.split_whitespace().map(str::parse).collect()
+}
+
+impl From<Decision> for Step {
+    fn from((node_1, node_2): Decision) -> Self {
+        Step::Fix(node_1, node_2)
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Fix(node_1, node_2) => write!(f, "{}-{}", node_1, node_2),
+            Step::Head(node, head) => write!(f, "h{}={}", node, head),
+            Step::Tail(node, tail) => write!(f, "t{}={}", node, tail),
+        }
+    }
+}
+
+impl FromStr for Step {
+    type Err = String;
+
+    fn from_str(step: &str) -> Result<Self, String> {
+        let invalid = || format!("Not a step: {}", step);
+        let parse_window = |window: &str| -> Result<(usize, u32), String> {
+            let mut parts = window.splitn(2, '=');
+            match (parts.next().map(str::parse), parts.next().map(str::parse)) {
+                (Some(Ok(node)), Some(Ok(value))) => Ok((node, value)),
+                _ => Err(invalid())
+            }
+        };
+
+        if let Some(window) = step.strip_prefix('h') {
+            parse_window(window).map(|(node, head)| Step::Head(node, head))
+        } else if let Some(window) = step.strip_prefix('t') {
+            parse_window(window).map(|(node, tail)| Step::Tail(node, tail))
+        } else {
+            let mut ids = step.splitn(2, '-').map(str::parse);
+            match (ids.next(), ids.next()) {
+                (Some(Ok(node_1)), Some(Ok(node_2))) => Ok(Step::Fix(node_1, node_2)),
+                _ => Err(invalid())
+            }
+        }
+    }
+}
+
+impl<G> Display for ReplayFailure<G> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.step {
+            Some(step) => write!(f, "Step {} failed: {}", step, self.reason),
+            None => write!(f, "The root is infeasible: {}", self.reason),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Restart schedules for the branch and bound.
+//!
+//! A restart throws away the open nodes and starts again from the root,
+//! the incumbent and the upper bound are kept. Combined with random tie-breaking
+//! in the branching this keeps the depth first search from being stuck in a bad subtree.
+
+/// How many node evaluations a run gets before the search restarts.
+#[derive(Clone, Copy, Debug)]
+pub enum Restarts {
+    /// Run i gets `scale * luby(i)` node evaluations: 1, 1, 2, 1, 1, 2, 4, 1, ...
+    Luby(usize),
+    /// Run i gets `base * factor^i` node evaluations.
+    Geometric(usize, f64),
+}
+
+pub struct RestartSchedule {
+    restarts: Restarts,
+    run: u32,
+}
+
+impl RestartSchedule {
+    pub fn new(restarts: Restarts) -> Self {
+        RestartSchedule { restarts, run: 0 }
+    }
+
+    /// Node limit of the next run
+    pub fn next_limit(&mut self) -> usize {
+        self.run += 1;
+        match self.restarts {
+            Restarts::Luby(scale) => scale * luby(self.run) as usize,
+            Restarts::Geometric(base, factor) => (base as f64 * factor.powi(self.run as i32 - 1)) as usize,
+        }
+    }
+}
+
+/// The i-th (starting at 1) element of the Luby sequence.
+///
+/// luby(i) = 2^(k-1) if i = 2^k - 1, otherwise luby(i - 2^(k-1) + 1) where 2^(k-1) <= i < 2^k - 1
+pub fn luby(mut i: u32) -> u32 {
+    debug_assert!(i > 0);
+    loop {
+        let mut k = 1;
+        while (1 << k) - 1 < i {
+            k += 1;
+        }
+
+        if (1 << k) - 1 == i {
+            return 1 << (k - 1);
+        }
+        i = i - (1 << (k - 1)) + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_luby() {
+        let sequence = (1..=15).map(luby).collect::<Vec<_>>();
+        assert_eq!(vec!(1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8), sequence);
+    }
+
+    #[test]
+    fn test_geometric() {
+        let mut schedule = RestartSchedule::new(Restarts::Geometric(100, 1.5));
+        let limits = (0..4).map(|_| schedule.next_limit()).collect::<Vec<_>>();
+        assert_eq!(vec!(100, 150, 225, 337), limits);
+    }
+}
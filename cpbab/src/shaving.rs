@@ -0,0 +1,120 @@
+//! Shaving, or singleton consistency, on the heads and tails.
+//!
+//! The start window of an operation is tentatively restricted to one end of its window,
+//! propagation is run on a copy of the graph. If that is infeasible the operation
+//! can not start in that part of its window and the head or tail is tightened for good.
+//! The restricted part is halved until a feasible restriction is found.
+use disjunctgraph::{ Graph, ConstrainedNode, GraphNode, NodeId };
+
+use crate::propagation;
+
+/// When shaving is applied during the search
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Shaving {
+    #[default]
+    Off,
+    /// Only shave the root, before the search starts.
+    Root,
+    /// Shave the root and every node after propagating its fixation.
+    Nodes,
+}
+
+/// Shave all operations until no head or tail changes.
+/// Returns whether any head or tail was tightened, or an error when the graph is infeasible.
+pub fn shave<I: Graph + Clone + std::fmt::Debug>(graph: &mut I, upper_bound: u32) -> Result<bool, String> where I::Node: ConstrainedNode + std::fmt::Debug {
+    let operations = graph.nodes().iter()
+        .filter(|n| n.machine_id().is_some())
+        .map(|n| n.id())
+        .collect::<Vec<_>>();
+
+    let mut changed = false;
+    let mut change_occured = true;
+    while change_occured {
+        change_occured = false;
+        for operation in &operations {
+            change_occured |= shave_head(graph, *operation, upper_bound)?;
+            change_occured |= shave_tail(graph, *operation, upper_bound)?;
+        }
+        changed |= change_occured;
+    }
+
+    Ok(changed)
+}
+
+/// Try to start the operation in the first part of its window, [head, head + delta].
+/// If that fails the head is at least head + delta + 1.
+fn shave_head<I: Graph + Clone + std::fmt::Debug>(graph: &mut I, operation: usize, upper_bound: u32) -> Result<bool, String> where I::Node: ConstrainedNode + std::fmt::Debug {
+    let mut changed = false;
+    let mut delta = slack(&graph[operation], upper_bound) / 2;
+
+    while slack(&graph[operation], upper_bound) > 0 {
+        let node = &graph[operation];
+        let (head, weight) = (node.head(), node.weight());
+
+        let mut probe = graph.clone();
+        probe[operation].set_tail(upper_bound - (head + delta + weight));
+        if consistent(&mut probe, operation, upper_bound) {
+            if delta == 0 {
+                break;
+            }
+            delta /= 2;
+        } else {
+            let new_head = head + delta + 1;
+            if new_head + weight + graph[operation].tail() > upper_bound {
+                return Err(format!("Shaving head of {} to {} would lead to infeasability", operation, new_head));
+            }
+            graph[operation].set_head(new_head);
+            propagation::propagate_head(&operation, graph, upper_bound)?;
+            changed = true;
+            delta = slack(&graph[operation], upper_bound) / 2;
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Try to start the operation in the last part of its window, [lst - delta, lst].
+/// If that fails the latest start is at most lst - delta - 1.
+fn shave_tail<I: Graph + Clone + std::fmt::Debug>(graph: &mut I, operation: usize, upper_bound: u32) -> Result<bool, String> where I::Node: ConstrainedNode + std::fmt::Debug {
+    let mut changed = false;
+    let mut delta = slack(&graph[operation], upper_bound) / 2;
+
+    while slack(&graph[operation], upper_bound) > 0 {
+        let node = &graph[operation];
+        let (tail, weight) = (node.tail(), node.weight());
+        let lst = node.lst(upper_bound);
+
+        let mut probe = graph.clone();
+        probe[operation].set_head(lst - delta);
+        if consistent(&mut probe, operation, upper_bound) {
+            if delta == 0 {
+                break;
+            }
+            delta /= 2;
+        } else {
+            let new_tail = tail + delta + 1;
+            if graph[operation].head() + weight + new_tail > upper_bound {
+                return Err(format!("Shaving tail of {} to {} would lead to infeasability", operation, new_tail));
+            }
+            graph[operation].set_tail(new_tail);
+            propagation::propagate_tail(&operation, graph, upper_bound)?;
+            changed = true;
+            delta = slack(&graph[operation], upper_bound) / 2;
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Propagate the restricted window of the operation, false if it leads to infeasability
+fn consistent<I: Graph + std::fmt::Debug>(probe: &mut I, operation: usize, upper_bound: u32) -> bool where I::Node: ConstrainedNode + std::fmt::Debug {
+    let result = propagation::propagate_head(&operation, probe, upper_bound)
+        .and_then(|_| propagation::propagate_tail(&operation, probe, upper_bound))
+        .and_then(|_| propagation::propagate(probe, upper_bound));
+
+    result.is_ok()
+}
+
+fn slack<N: ConstrainedNode>(node: &N, upper_bound: u32) -> u32 {
+    upper_bound - node.head() - node.weight() - node.tail()
+}
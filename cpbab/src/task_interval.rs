@@ -28,136 +28,298 @@ impl<'a, T: Graph> TaskInterval<'a, T> where T::Node: ConstrainedNode + std::fmt
         self.upper() - self.lower() - self.processing
     }
 
-    /// In this task interval, there should be enough space to execute all operations    
-    fn feasible(&self) -> bool {
-        self.upper_bound >= self.lower.head() + self.processing + self.upper.tail()
-    }
-
     pub fn upper(&self) -> u32 { self.upper.lct(self.upper_bound) }
     pub fn lower(&self) -> u32 { self.lower.head() }
+}
 
-    pub fn from_interval<'b>(graph: &T, resource: &[&'b T::Node], lower: &'b T::Node, upper: &'b T::Node, upper_bound: u32) -> Result<Option<TaskInterval<'b, T>>, String> {
+/*
+pub fn propagate_task_interval<T>(resources: &[usize], graph: &mut T) where T::Node: ConstrainedNode + std::fmt::Debug {
+    
+}*/
+/// The task intervals of one resource, kept up to date with the graph instead of being rebuilt.
+/// Based on the interval maintenance of Y. Caseau and F. Laburthe.
+///
+/// An interval is defined by the operations `lower` and `upper`, it contains all operations
+/// with head >= head(lower) and tail >= tail(upper). After heads, tails or orders change
+/// `sync` only revisits the intervals that can be affected by the changed operations.
+#[derive(Clone, Debug)]
+pub struct ResourceIntervals {
+    resource: u32,
+    upper_bound: u32,
+    operations: Vec<usize>,
+    weights: Vec<u32>,
+    heads: Vec<u32>,
+    tails: Vec<u32>,
+    /// Pairs of operations (as index in `operations`) that still have a disjunction
+    open: Vec<(usize, usize)>,
+    /// Interval lower * n + upper
+    intervals: Vec<Interval>,
+}
 
-        // Task intervals should contain operations that have no disjunctions left
-        let nodes: Vec<&T::Node> = resource.iter()
-            .filter(|node| node.head() >= lower.head() && node.tail() >= upper.tail())
-            .cloned().collect();
+#[derive(Clone, Debug)]
+pub struct Interval {
+    lower: usize,
+    upper: usize,
+    lower_bound: u32,
+    upper_bound: u32,
+    active: bool,
+    pub processing: u32,
+    pub nc_start: Vec<usize>,
+    pub nc_end: Vec<usize>,
+}
+
+impl Interval {
+    pub fn lower(&self) -> u32 { self.lower_bound }
+    pub fn upper(&self) -> u32 { self.upper_bound }
+
+    pub fn slack(&self) -> u32 {
+        self.upper() - self.lower() - self.processing
+    }
+
+    pub fn num_choices(&self) -> usize {
+        std::cmp::min(self.nc_start.len(), self.nc_end.len())
+    }
+}
 
-        // TaskIntervals must have by definition 2 or more nodes.
-        if nodes.len() < 2 {
-            return Ok(None);
+impl ResourceIntervals {
+    pub fn new<T: Graph>(resource: u32, graph: &T, upper_bound: u32) -> Result<Self, String> where T::Node: ConstrainedNode {
+        let operations = graph.nodes().iter().filter(|x| x.machine_id() == Some(resource)).map(|x| x.id()).collect_vec();
+        let n = operations.len();
+
+        let open = (0..n).tuple_combinations()
+            .filter(|(a, b)| graph.has_disjunction(&operations[*a], &operations[*b]))
+            .collect_vec();
+
+        let mut intervals = ResourceIntervals {
+            resource,
+            upper_bound,
+            weights: operations.iter().map(|o| graph[*o].weight()).collect(),
+            heads: operations.iter().map(|o| graph[*o].head()).collect(),
+            tails: operations.iter().map(|o| graph[*o].tail()).collect(),
+            operations,
+            open,
+            intervals: Vec::with_capacity(n * n),
+        };
+
+        for (lower, upper) in (0..n).cartesian_product(0..n) {
+            let processing = intervals.processing(lower, upper);
+            intervals.intervals.push(Interval {
+                lower, upper, processing,
+                lower_bound: 0, upper_bound: 0,
+                active: false,
+                nc_start: Vec::new(), nc_end: Vec::new()
+            });
         }
 
-        let processing = nodes.iter().map(|x| x.weight()).sum();
-
-        // NC start is the set of jobs that can be executed before all jobs in S. This is the edge finding part in the paper:
-        // If t was the first in the interval, is there enough space to execute the other nodes?
-        // if it has any "node -> other" then node can't be first 
-        // all nodes where head(n) + d(S) + tail(S) fits in the upper_bound
-        
-        // upper_bound >= tail(S) + head(t) + d(S)
-        let nc_start = nodes.iter()
-            .filter(|node| upper_bound >= node.head() + processing + upper.tail())
-            .filter(|node| !nodes.iter().any(|other| graph.has_precedence(*other, **node)))
-            .map(|x| *x)
-            .collect::<Vec<_>>();
-
-        // if nc_start.len() == 0 {
-        //     return None;
-        // }
-
-        if nc_start.len() == 0 {
-            return Err(format!("Task interval on resource {} with l: {} u: {} is infeasible, no nodes on start.",
-            lower.machine_id().unwrap(), lower.id(), upper.id()));
+        for i in 0..intervals.intervals.len() {
+            intervals.refresh(i, graph)?;
         }
 
-        debug_assert!(nc_start.len() >= 1, "An interval can always have a first node:
-        \n{:?}
-        \nstart nodes 1: \n{:?}
-        \nstart nodes 2: \n{:?}
-        \nnodes: {:?}
-        \nub: {}", 
-        graph, 
-        nodes.iter().filter(|n| upper_bound >= n.head() + processing + upper.tail()).map(|n| n.id()).collect_vec(), 
-        nodes.iter().filter(|n| !nodes.iter().any(|other| graph.has_precedence(*other, **n))).map(|n| n.id()).collect_vec(),
-        nodes.iter().map(|n| n.id()).collect_vec(),
-        upper_bound);
-       
-        
-        // NC end is the set of jobs that can be executed after all jobs in S.        
-        // If t was the last interval, is there enough space to execute the other nodes?
-        // if it has any "other -> node" then node can't be last
-        // all nodes where head(S) + d(S) + tail(n) fits in the upper_bound
-        
-        let nc_end = nodes.iter()
-            .filter(|node| upper_bound >= lower.head() + processing + node.tail())            
-            .filter(|node| !nodes.iter().any(|other| graph.has_precedence(**node, *other)))            
-            .map(|x| *x)
-            .collect::<Vec<_>>();
-
-        
-        if nc_end.len() == 0 {
-            return Err(format!("Task interval on resource {} with l: {} u: {} is infeasible, no nodes on end.",
-            lower.machine_id().unwrap(), lower.id(), upper.id()));
+        Ok(intervals)
+    }
+
+    pub fn resource(&self) -> u32 { self.resource }
+
+    /// Active task intervals: at least two operations and a non empty window.
+    pub fn intervals(&self) -> impl Iterator<Item = &Interval> {
+        self.intervals.iter().filter(|i| i.active)
+    }
+
+    /// Bring the intervals up to date with the heads, tails and orders in the graph.
+    /// Errors when an interval shows that the resource can not be scheduled.
+    pub fn sync<T: Graph>(&mut self, graph: &T, upper_bound: u32) -> Result<(), String> where T::Node: ConstrainedNode {
+        let n = self.operations.len();
+        let mut dirty = vec!(upper_bound != self.upper_bound; n * n);
+        self.upper_bound = upper_bound;
+
+        for k in 0..n {
+            let node = &graph[self.operations[k]];
+            let (head, tail) = (node.head(), node.tail());
+            if head == self.heads[k] && tail == self.tails[k] {
+                continue;
+            }
+
+            let (old_head, old_tail) = (self.heads[k], self.tails[k]);
+            self.heads[k] = head;
+            self.tails[k] = tail;
+
+            for (lower, upper) in (0..n).cartesian_product(0..n) {
+                let i = lower * n + upper;
+                if lower == k || upper == k {
+                    // Bounds changed, the members can change too
+                    self.intervals[i].processing = self.processing(lower, upper);
+                    dirty[i] = true;
+                } else {
+                    let was_member = old_head >= self.heads[lower] && old_tail >= self.tails[upper];
+                    let is_member = self.is_member(k, lower, upper);
+                    match (was_member, is_member) {
+                        (false, true) => self.intervals[i].processing += self.weights[k],
+                        (true, false) => self.intervals[i].processing -= self.weights[k],
+                        _ => ()
+                    }
+                    dirty[i] |= was_member || is_member;
+                }
+            }
         }
 
+        // Fixed disjunctions change which operations can be first or last
+        let operations = &self.operations;
+        let (open, fixed): (Vec<_>, Vec<_>) = self.open.iter()
+            .partition(|(a, b)| graph.has_disjunction(&operations[*a], &operations[*b]));
+        self.open = open;
+        for (a, b) in fixed {
+            for (lower, upper) in (0..n).cartesian_product(0..n) {
+                if self.is_member(a, lower, upper) && self.is_member(b, lower, upper) {
+                    dirty[lower * n + upper] = true;
+                }
+            }
+        }
 
-        debug_assert!(nc_end.len() >= 1, "An interval can always have a last node: \n{:?}\nend nodes: {:?}\nub: {}", 
-        graph, nodes.iter().filter(|n: &&&T::Node| upper_bound >= n.tail() + processing + lower.head()).map(|n| n.id()).collect_vec(), upper_bound);
+        for i in dirty.into_iter().positions(|d| d) {
+            self.refresh(i, graph)?;
+        }
 
-        let ti = TaskInterval {
-            upper_bound, upper, lower,
-            processing, nodes,
-            nc_start, nc_end
-        };
+        Ok(())
+    }
 
-        if !ti.feasible() {
-            return Err(format!("Task interval on resource {} with l: {} u: {} is infeasible, negative slack",
-            lower.machine_id().unwrap(), lower.id(), upper.id()));
-        }       
-        
-        
-        debug_assert!(ti.nc_start.iter().combinations(2).all(|x| graph.has_disjunction(*x[0], *x[1])), 
-            "All nodes within nc_start should have disjunctions between each other, {}", ti.nc_start.len());
-       
-        debug_assert!(ti.nc_end.iter().combinations(2).all(|x| graph.has_disjunction(*x[0], *x[1])),
-            "All nodes within nc_end should have disjunctions between each other, {}", ti.nc_start.len());
-
-        Ok(Some(ti))
+    /// Create the task interval with node references, for the branching heuristics.
+    pub fn task_interval<'a, T: Graph>(&self, interval: &Interval, graph: &'a T) -> TaskInterval<'a, T> where T::Node: ConstrainedNode + std::fmt::Debug {
+        let member = |id: &usize| &graph[*id];
+        TaskInterval {
+            upper_bound: self.upper_bound,
+            upper: &graph[self.operations[interval.upper]],
+            lower: &graph[self.operations[interval.lower]],
+            processing: interval.processing,
+            nc_start: interval.nc_start.iter().map(member).collect(),
+            nc_end: interval.nc_end.iter().map(member).collect(),
+            nodes: (0..self.operations.len())
+                .filter(|k| self.is_member(*k, interval.lower, interval.upper))
+                .map(|k| &graph[self.operations[k]])
+                .collect(),
+        }
     }
-}
 
-pub fn find_task_intervals<T: Graph>(resource: u32, graph: &T, upper_bound: u32) -> Result<Vec<TaskInterval<T>>, String> where T::Node: ConstrainedNode + std::fmt::Debug, T: std::fmt::Debug {
-    
-    let operations = graph.nodes().iter().filter(|x| x.machine_id() == Some(resource)).collect_vec();
-    let ests: Vec<&T::Node> = operations.iter().unique_by(|s| s.head()).sorted_by_key(|s| s.head()).cloned().collect_vec();
-    let lcts: Vec<&T::Node> = operations.iter().unique_by(|s| s.tail()).sorted_by_key(|s| std::cmp::Reverse(s.tail())).cloned().collect_vec();
-    
-    let mut j = 0;
-    let mut task_intervals: Vec<TaskInterval<T>> = Vec::with_capacity(operations.len().pow(2));
+    fn is_member(&self, k: usize, lower: usize, upper: usize) -> bool {
+        self.heads[k] >= self.heads[lower] && self.tails[k] >= self.tails[upper]
+    }
+
+    fn processing(&self, lower: usize, upper: usize) -> u32 {
+        (0..self.operations.len())
+            .filter(|k| self.is_member(*k, lower, upper))
+            .map(|k| self.weights[k])
+            .sum()
+    }
 
-    for i in 0..ests.len() {
-        let lower = ests[i];
+    /// Recompute the bounds, nc_start and nc_end of an interval, shared by `new` and `sync`.
+    /// An interval is active with at least two members and a non empty window. Members that fit
+    /// before or after the others and have no member ordered before or after them can be first or last.
+    fn refresh<T: Graph>(&mut self, i: usize, graph: &T) -> Result<(), String> where T::Node: ConstrainedNode {
+        let n = self.operations.len();
+        let (lower, upper) = (self.intervals[i].lower, self.intervals[i].upper);
+        let (resource, upper_bound) = (self.resource, self.upper_bound);
+        let (head, tail) = (self.heads[lower], self.tails[upper]);
 
-        // Find first j that has a upper_bound - tail(j) > head(i)        
-        while j < lcts.len() && upper_bound <= lower.head() + lcts[j].tail() {        
-            j += 1;
+        let members = (0..n).filter(|k| self.is_member(*k, lower, upper)).collect_vec();
+        let processing = self.intervals[i].processing;
+        debug_assert_eq!(processing, self.processing(lower, upper));
+
+        let interval = &mut self.intervals[i];
+        interval.active = members.len() >= 2 && upper_bound > head + tail;
+        if !interval.active {
+            return Ok(());
         }
+        interval.lower_bound = head;
+        interval.upper_bound = upper_bound - tail;
 
-        for j in j..lcts.len() {
-            let upper = lcts[j];
-            debug_assert!(upper_bound > lower.head() + upper.tail());
+        if head + processing + tail > upper_bound {
+            return Err(format!("Task interval on resource {} with l: {} u: {} is infeasible, negative slack",
+                resource, self.operations[lower], self.operations[upper]));
+        }
 
-            if let Some(ti) = TaskInterval::from_interval(graph, &operations, lower, upper, upper_bound)? {
-                task_intervals.push(ti);
-            }
+        let operations = &self.operations;
+        let (heads, tails) = (&self.heads, &self.tails);
+
+        // Can be first: fits before the others and no other member precedes it
+        interval.nc_start = members.iter()
+            .filter(|k| upper_bound >= heads[**k] + processing + tail)
+            .filter(|k| !members.iter().any(|other| graph.has_precedence(&operations[*other], &operations[**k])))
+            .map(|k| operations[*k])
+            .collect();
+
+        // Can be last: fits after the others and it precedes no other member
+        interval.nc_end = members.iter()
+            .filter(|k| upper_bound >= head + processing + tails[**k])
+            .filter(|k| !members.iter().any(|other| graph.has_precedence(&operations[**k], &operations[*other])))
+            .map(|k| operations[*k])
+            .collect();
+
+        if interval.nc_start.is_empty() || interval.nc_end.is_empty() {
+            return Err(format!("Task interval on resource {} with l: {} u: {} is infeasible, no nodes on start or end.",
+                resource, operations[lower], operations[upper]));
         }
+
+        Ok(())
     }
-    
-    Ok(task_intervals)
 }
 
-/*
-pub fn propagate_task_interval<T>(resources: &[usize], graph: &mut T) where T::Node: ConstrainedNode + std::fmt::Debug {
-    
-}*/
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use disjunctgraph::Relation;
+    use crate::{ CGraph, node::Node, propagation };
+
+    /// Three single operation jobs on one machine, between source 0 and sink 4.
+    fn single_machine() -> CGraph {
+        let nodes = vec!(
+            Node::create(0, 0, None, None),
+            Node::create(1, 2, Some(1), Some(0)),
+            Node::create(2, 3, Some(1), Some(1)),
+            Node::create(3, 4, Some(1), Some(2)),
+            Node::create(4, 0, None, None));
+
+        let mut edges = vec!(Vec::new(); 5);
+        for op in 1..=3 {
+            edges[0].push(Relation::Successor(op));
+            edges[op].push(Relation::Predecessor(0));
+            edges[op].push(Relation::Successor(4));
+            edges[4].push(Relation::Predecessor(op));
+            for other in (1..=3).filter(|o| *o != op) {
+                edges[op].push(Relation::Disjunctive(other));
+            }
+        }
+
+        let mut graph = CGraph::create(nodes, edges);
+        graph.init_weights();
+        graph
+    }
+
+    type Summary = (u32, u32, u32, Vec<usize>, Vec<usize>);
+
+    fn summary(intervals: &ResourceIntervals) -> Vec<Summary> {
+        intervals.intervals()
+            .map(|i| (i.lower(), i.upper(), i.processing, i.nc_start.clone(), i.nc_end.clone()))
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn test_sync_matches_rebuild() {
+        let upper_bound = 12;
+        let mut graph = single_machine();
+        let mut intervals = ResourceIntervals::new(1, &graph, upper_bound).unwrap();
+        // All three operations in [0, 12] with a processing time of 9
+        assert_eq!(Some(3), intervals.intervals().map(|i| i.slack()).min());
+
+        graph.fix_disjunction(&1, &2).unwrap();
+        propagation::propagate_fixation(&mut graph, &1, &2, upper_bound).unwrap();
+        intervals.sync(&graph, upper_bound).unwrap();
+
+        let rebuilt = ResourceIntervals::new(1, &graph, upper_bound).unwrap();
+        assert_eq!(summary(&rebuilt), summary(&intervals));
+
+        // A smaller upper bound only changes the windows
+        intervals.sync(&graph, 10).unwrap();
+        let rebuilt = ResourceIntervals::new(1, &graph, 10).unwrap();
+        assert_eq!(summary(&rebuilt), summary(&intervals));
+    }
+}
@@ -0,0 +1,176 @@
+//! Recording of the search tree, to look at the behaviour of the search after it has run.
+//!
+//! Every node the search creates is recorded with its parent, the decision that created it, its lower bound,
+//! the total slack of its operations and what became of it. The tree is written as JSON or as a Graphviz DOT graph.
+//! Every run after a restart starts a new root.
+use std::io::{ self, Write };
+use std::sync::Mutex;
+
+use crate::nogood::Decision;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeOutcome {
+    /// Not evaluated before the run ended
+    Open,
+    /// Expanded into children
+    Branched,
+    /// The lower bound exceeds the upper bound
+    PrunedByBound,
+    /// Propagation failed or the orientation has a cycle
+    Infeasible,
+    /// Contains a recorded nogood
+    Nogood,
+    /// The same selection was searched before
+    Transposition,
+    /// A complete schedule that improved the upper bound, with its makespan
+    Solution(u32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeNode {
+    pub parent: Option<usize>,
+    /// The run of the search, counted from 1
+    pub run: usize,
+    /// The upper bound when the node was created, the node is reproduced by `replay` under this bound
+    pub upper_bound: u32,
+    /// The disjunction fixed from the parent, none for the roots
+    pub decision: Option<Decision>,
+    /// None when the branch failed before its bounds were known
+    pub lower_bound: Option<u32>,
+    /// Sum of the slack of all operations under the upper bound when the node was created
+    pub slack: Option<u32>,
+    pub outcome: NodeOutcome,
+}
+
+/// The nodes of a search, a node is identified by its index.
+#[derive(Clone, Debug, Default)]
+pub struct SearchTree {
+    nodes: Vec<TreeNode>,
+}
+
+impl SearchTree {
+    pub fn new() -> Self {
+        SearchTree::default()
+    }
+
+    pub fn nodes(&self) -> &[TreeNode] {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    pub fn count(&self, outcome: NodeOutcome) -> usize {
+        self.nodes.iter().filter(|node| node.outcome == outcome).count()
+    }
+
+    /// The decisions from the root of its run to the node, they are replayed with `replay`.
+    /// Nodes resumed from a checkpoint are roots that only keep their last decision, paths below them are partial.
+    pub fn decisions(&self, id: usize) -> Vec<Decision> {
+        let mut decisions = Vec::new();
+        let mut next = Some(id);
+        while let Some(node) = next.map(|id| &self.nodes[id]) {
+            decisions.extend(node.decision);
+            next = node.parent;
+        }
+        decisions.reverse();
+        decisions
+    }
+
+    /// The nodes as an array of objects, the outcome is a string and `makespan` is only set for solutions.
+    pub fn write_json<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "{{\"nodes\": [")?;
+        for (id, node) in self.nodes.iter().enumerate() {
+            let decision = node.decision.map_or("null".to_owned(), |(a, b)| format!("[{}, {}]", a, b));
+            let makespan = match node.outcome {
+                NodeOutcome::Solution(makespan) => makespan.to_string(),
+                _ => "null".to_owned()
+            };
+            write!(w, "  {{\"id\": {}, \"parent\": {}, \"run\": {}, \"upper_bound\": {}, \"decision\": {}, \"lower_bound\": {}, \"slack\": {}, \"outcome\": \"{}\", \"makespan\": {}}}",
+                id, json(node.parent), node.run, node.upper_bound, decision, json(node.lower_bound), json(node.slack), outcome_name(node.outcome), makespan)?;
+            writeln!(w, "{}", if id + 1 < self.nodes.len() { "," } else { "" })?;
+        }
+        writeln!(w, "]}}")
+    }
+
+    /// Edges are labelled with the decision, nodes are colored by their outcome.
+    pub fn write_dot<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "digraph search {{")?;
+        writeln!(w, "    node[shape=box, style=filled];")?;
+        for (id, node) in self.nodes.iter().enumerate() {
+            let mut label = format!("{}\\n{}", id, outcome_name(node.outcome));
+            if let NodeOutcome::Solution(makespan) = node.outcome {
+                label += &format!(": {}", makespan);
+            }
+            if let Some(lower_bound) = node.lower_bound {
+                label += &format!("\\nlb: {}", lower_bound);
+            }
+            if let Some(slack) = node.slack {
+                label += &format!("\\nslack: {}", slack);
+            }
+            writeln!(w, "    n{}[label=\"{}\", fillcolor=\"{}\"];", id, label, outcome_color(node.outcome))?;
+        }
+        for (id, node) in self.nodes.iter().enumerate() {
+            if let (Some(parent), Some((a, b))) = (node.parent, node.decision) {
+                writeln!(w, "    n{} -> n{}[label=\"{} -> {}\"];", parent, id, a, b)?;
+            }
+        }
+        writeln!(w, "}}")
+    }
+}
+
+/// Records nodes while a tree is given, otherwise does nothing. The tree is only locked to record a node.
+pub(crate) struct Recorder<'a>(pub Option<&'a Mutex<SearchTree>>);
+
+impl<'a> Recorder<'a> {
+    /// The node is only built when recording, its id is returned.
+    pub fn add(&self, node: impl FnOnce() -> TreeNode) -> Option<usize> {
+        self.0.map(|tree| {
+            let mut tree = tree.lock().expect("Search tree is poisoned");
+            tree.nodes.push(node());
+            tree.nodes.len() - 1
+        })
+    }
+
+    pub fn close(&self, id: Option<usize>, outcome: NodeOutcome) {
+        if let (Some(tree), Some(id)) = (self.0, id) {
+            tree.lock().expect("Search tree is poisoned").nodes[id].outcome = outcome;
+        }
+    }
+}
+
+fn json(value: Option<impl ToString>) -> String {
+    value.map_or("null".to_owned(), |v| v.to_string())
+}
+
+fn outcome_name(outcome: NodeOutcome) -> &'static str {
+    match outcome {
+        NodeOutcome::Open => "open",
+        NodeOutcome::Branched => "branched",
+        NodeOutcome::PrunedByBound => "pruned",
+        NodeOutcome::Infeasible => "infeasible",
+        NodeOutcome::Nogood => "nogood",
+        NodeOutcome::Transposition => "transposition",
+        NodeOutcome::Solution(_) => "solution",
+    }
+}
+
+fn outcome_color(outcome: NodeOutcome) -> &'static str {
+    match outcome {
+        NodeOutcome::Open => "white",
+        NodeOutcome::Branched => "lightblue",
+        NodeOutcome::PrunedByBound => "lightgray",
+        NodeOutcome::Infeasible => "lightpink",
+        NodeOutcome::Nogood | NodeOutcome::Transposition => "khaki",
+        NodeOutcome::Solution(_) => "palegreen",
+    }
+}
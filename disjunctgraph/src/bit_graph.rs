@@ -0,0 +1,366 @@
+use crate::{ NodeId, GraphNode, ConstrainedNode, NodeIterator, Graph, Relation, GraphError, self as disjunctgraph };
+use crate::transposition::arc_key;
+use crate::order::TopologicalOrder;
+
+// Every node has a bitset of its successors, predecessors and disjunctions.
+// The bitsets of all nodes are stored row after row in one vector per relation,
+// so a relation is a single bit test and iterating a row walks a few words.
+
+#[derive(Clone)]
+pub struct BitGraph<T: NodeId + Clone> {
+    nodes: Vec<T>,
+    /// Words per row
+    words: usize,
+    successors: Vec<u64>,
+    predecessors: Vec<u64>,
+    disjunctions: Vec<u64>,
+    /// Successors that were disjunctions
+    fixed: Vec<u64>,
+    hash: u64,
+    /// Topological order of the precedences, to keep cycle checks local
+    order: TopologicalOrder
+}
+
+impl<T: NodeId + GraphNode + Clone> Graph for BitGraph<T> {
+    type Node = T;
+
+    fn create(nodes: Vec<T>, edges: Vec<Vec<Relation>>) -> Self {
+        let words = nodes.len().div_ceil(64);
+        let size = nodes.len() * words;
+
+        let mut graph = BitGraph {
+            nodes,
+            words,
+            successors: vec!(0; size),
+            predecessors: vec!(0; size),
+            disjunctions: vec!(0; size),
+            fixed: vec!(0; size),
+            hash: 0,
+            order: TopologicalOrder::default()
+        };
+
+        for (row, relations) in edges.iter().enumerate() {
+            for relation in relations {
+                match relation {
+                    Relation::Successor(e) => {
+                        insert(&mut graph.successors, words, row, *e);
+                        graph.hash ^= arc_key(row, *e);
+                    },
+                    Relation::Predecessor(e) => insert(&mut graph.predecessors, words, row, *e),
+                    Relation::Disjunctive(e) => insert(&mut graph.disjunctions, words, row, *e),
+                }
+            }
+        }
+
+        graph.order = TopologicalOrder::new(&graph);
+        graph
+    }
+
+    fn nodes(&self) -> &[T] {
+        &self.nodes
+    }
+
+    fn nodes_mut(&mut self) -> &mut [T] {
+        &mut self.nodes
+    }
+
+    fn source(&self) -> &T {
+        self.nodes().first().unwrap()
+    }
+
+    fn sink(&self) -> &T {
+        self.nodes().last().unwrap()
+    }
+
+    fn successors(&self, id: &impl NodeId) -> NodeIterator<'_, Self> {
+        NodeIterator::bits(&self.nodes, Bits::new(self.row(&self.successors, id.id())))
+    }
+
+    fn predecessors(&self, id: &impl NodeId) -> NodeIterator<'_, Self> {
+        NodeIterator::bits(&self.nodes, Bits::new(self.row(&self.predecessors, id.id())))
+    }
+
+    fn disjunctions(&self, id: &impl NodeId) -> NodeIterator<'_, Self> {
+        NodeIterator::bits(&self.nodes, Bits::new(self.row(&self.disjunctions, id.id())))
+    }
+
+    fn fix_disjunction(&mut self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<(), GraphError> {
+        if !self.has_disjunction(node_1, node_2) {
+            return Err(GraphError::InvalidEdge);
+        }
+
+        let node_1 = node_1.id();
+        let node_2 = node_2.id();
+
+        // Node_2 already reaches node_1
+        if let Some(cycle) = self.precedence_path(&node_2, &node_1) {
+            return Err(GraphError::Cyclic(cycle));
+        }
+
+        // Remove from disjunctions
+        remove(&mut self.disjunctions, self.words, node_1, node_2);
+        remove(&mut self.disjunctions, self.words, node_2, node_1);
+
+        // Node_1 -> Node_2
+        insert(&mut self.successors, self.words, node_1, node_2);
+        insert(&mut self.predecessors, self.words, node_2, node_1);
+        insert(&mut self.fixed, self.words, node_1, node_2);
+        self.hash ^= arc_key(node_1, node_2);
+
+        let affected = self.order.affected(self, node_1, node_2);
+        self.order.reorder(affected);
+
+        Ok(())
+    }
+
+    fn unfix_disjunction(&mut self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<(), GraphError> {
+        if !self.has_fixed_disjunction(node_1, node_2) {
+            return Err(GraphError::InvalidEdge);
+        }
+
+        let node_1 = node_1.id();
+        let node_2 = node_2.id();
+
+        remove(&mut self.fixed, self.words, node_1, node_2);
+        remove(&mut self.successors, self.words, node_1, node_2);
+        remove(&mut self.predecessors, self.words, node_2, node_1);
+        self.hash ^= arc_key(node_1, node_2);
+
+        insert(&mut self.disjunctions, self.words, node_1, node_2);
+        insert(&mut self.disjunctions, self.words, node_2, node_1);
+
+        Ok(())
+    }
+
+    fn flip_edge(mut self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<Self, GraphError> {
+        if !self.has_precedence(node_1, node_2) {
+            return Err(GraphError::InvalidEdge);
+        }
+
+        let node_1 = node_1.id();
+        let node_2 = node_2.id();
+
+        // node_1 -> node_2
+        remove(&mut self.successors, self.words, node_1, node_2);
+        remove(&mut self.predecessors, self.words, node_2, node_1);
+
+        // Node_1 reaches node_2 in another way
+        if let Some(cycle) = self.precedence_path(&node_1, &node_2) {
+            return Err(GraphError::Cyclic(cycle));
+        }
+
+        insert(&mut self.predecessors, self.words, node_1, node_2);
+        insert(&mut self.successors, self.words, node_2, node_1);
+        if contains(&self.fixed, self.words, node_1, node_2) {
+            remove(&mut self.fixed, self.words, node_1, node_2);
+            insert(&mut self.fixed, self.words, node_2, node_1);
+        }
+        self.hash ^= arc_key(node_1, node_2) ^ arc_key(node_2, node_1);
+
+        let affected = self.order.affected(&self, node_2, node_1);
+        self.order.reorder(affected);
+
+        Ok(self)
+    }
+
+    fn into_directed(&self) -> Result<Self, GraphError> {
+        let mut cloned = self.clone();
+        // Every disjunction goes from the lower to the higher id
+        for node_1 in 0..self.nodes.len() {
+            for node_2 in Bits::new(self.row(&self.disjunctions, node_1)).filter(|node_2| *node_2 > node_1) {
+                cloned.fix_disjunction(&node_1, &node_2)?;
+            }
+        }
+
+        match cloned.find_cycle() {
+            Some(cycle) => Err(disjunctgraph::GraphError::Cyclic(cycle)),
+            None => Ok(cloned)
+        }
+    }
+
+    /// Graph contains relation: node_1 -> node_2
+    fn has_precedence(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool {
+        contains(&self.successors, self.words, node_1.id(), node_2.id())
+    }
+
+    fn has_disjunction(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool {
+        contains(&self.disjunctions, self.words, node_1.id(), node_2.id())
+    }
+
+    fn has_fixed_disjunction(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool {
+        contains(&self.fixed, self.words, node_1.id(), node_2.id())
+    }
+
+    fn node_has_disjunction(&self, node: &impl NodeId) -> bool {
+        self.row(&self.disjunctions, node.id()).iter().any(|word| *word != 0)
+    }
+
+    fn precedence_path(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> Option<Vec<usize>> {
+        self.order.path(self, node_1.id(), node_2.id())
+    }
+
+    fn selection_hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl<T: NodeId + Clone> BitGraph<T> {
+    fn row<'a>(&self, set: &'a [u64], node: usize) -> &'a [u64] {
+        &set[node * self.words..(node + 1) * self.words]
+    }
+}
+
+fn insert(set: &mut [u64], words: usize, row: usize, column: usize) {
+    set[row * words + column / 64] |= 1 << (column % 64);
+}
+
+fn remove(set: &mut [u64], words: usize, row: usize, column: usize) {
+    set[row * words + column / 64] &= !(1 << (column % 64));
+}
+
+fn contains(set: &[u64], words: usize, row: usize, column: usize) -> bool {
+    set[row * words + column / 64] & (1 << (column % 64)) != 0
+}
+
+/// Indices of the set bits of a row
+pub(crate) struct Bits<'a> {
+    words: &'a [u64],
+    index: usize,
+    current: u64
+}
+
+impl<'a> Bits<'a> {
+    fn new(words: &'a [u64]) -> Self {
+        Bits { words, index: 0, current: words.first().cloned().unwrap_or(0) }
+    }
+}
+
+impl<'a> Iterator for Bits<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            if self.current != 0 {
+                let bit = self.current.trailing_zeros() as usize;
+                // Clear the lowest set bit
+                self.current &= self.current - 1;
+                return Some(self.index * 64 + bit);
+            }
+
+            self.index += 1;
+            self.current = *self.words.get(self.index)?;
+        }
+    }
+}
+
+impl<T: NodeId + Clone> std::ops::Index<usize> for BitGraph<T> {
+    type Output = T;
+
+    fn index(&self, node: usize) -> &Self::Output {
+        &self.nodes[node]
+    }
+}
+
+impl<T: NodeId + Clone> std::ops::IndexMut<usize> for BitGraph<T> {
+    fn index_mut(&mut self, node: usize) -> &mut T {
+        &mut self.nodes[node]
+    }
+}
+
+impl<T: ConstrainedNode + Clone> std::fmt::Debug for BitGraph<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "properties:")?;
+        for node in self.nodes() {
+            writeln!(f, "{}: [head: {}, tail: {}]", node.id(), node.head(), node.tail())?;
+        }
+
+        for (name, set) in &[("successors", &self.successors), ("predecessor", &self.predecessors), ("disjunction", &self.disjunctions)] {
+            writeln!(f, "{}:", name)?;
+            for node in self.nodes() {
+                writeln!(f, "{} -> {:?}", node.id(), Bits::new(self.row(set, node.id())).collect::<Vec<_>>())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LinkedGraph;
+
+    #[derive(Clone, Debug)]
+    struct Node(usize, u32);
+
+    impl NodeId for Node {
+        fn id(&self) -> usize { self.0 }
+    }
+
+    impl GraphNode for Node {
+        fn create(id: usize, weight: u32, _: Option<u32>, _: Option<usize>) -> Self { Node(id, weight) }
+        fn weight(&self) -> u32 { self.1 }
+        fn job_id(&self) -> Option<usize> { None }
+        fn machine_id(&self) -> Option<u32> { None }
+    }
+
+    /// Two chains from source to sink with a disjunction between every pair of their nodes
+    fn create<G: Graph<Node = Node>>(length: usize) -> G {
+        let n = 2 * length + 2;
+        let sink = n - 1;
+        let nodes = (0..n).map(|id| Node(id, id as u32 % 7)).collect::<Vec<_>>();
+        let mut edges = vec!(Vec::new(); n);
+
+        let arc = |edges: &mut Vec<Vec<Relation>>, a: usize, b: usize| {
+            edges[a].push(Relation::Successor(b));
+            edges[b].push(Relation::Predecessor(a));
+        };
+        for chain in 0..2 {
+            let ids = (0..length).map(|i| 1 + chain * length + i).collect::<Vec<_>>();
+            arc(&mut edges, 0, ids[0]);
+            ids.windows(2).for_each(|w| arc(&mut edges, w[0], w[1]));
+            arc(&mut edges, ids[length - 1], sink);
+        }
+        for a in 1..=length {
+            for b in (length + 1)..sink {
+                edges[a].push(Relation::Disjunctive(b));
+                edges[b].push(Relation::Disjunctive(a));
+            }
+        }
+
+        G::create(nodes, edges)
+    }
+
+    fn relations<G: Graph<Node = Node>>(graph: &G) -> Vec<(Vec<usize>, Vec<usize>, Vec<usize>)> {
+        let sorted = |iter: NodeIterator<G>| { let mut ids = iter.map(|n| n.id()).collect::<Vec<_>>(); ids.sort(); ids };
+        graph.nodes().iter()
+            .map(|n| (sorted(graph.successors(n)), sorted(graph.predecessors(n)), sorted(graph.disjunctions(n))))
+            .collect()
+    }
+
+    #[test]
+    fn test_matches_linked_graph() {
+        // Rows span more than one word
+        let mut bits: BitGraph<Node> = create(70);
+        let mut linked: LinkedGraph<Node> = create(70);
+        assert_eq!(relations(&linked), relations(&bits));
+
+        for (node_1, node_2) in &[(1, 71), (70, 140), (64, 128)] {
+            bits.fix_disjunction(node_1, node_2).unwrap();
+            linked.fix_disjunction(node_1, node_2).unwrap();
+        }
+        assert_eq!(relations(&linked), relations(&bits));
+        assert_eq!(linked.selection_hash(), bits.selection_hash());
+        assert!(bits.fix_disjunction(&71, &1).is_err());
+
+        let bits = bits.flip_edge(&64, &128).unwrap();
+        let linked = linked.flip_edge(&64, &128).unwrap();
+        assert_eq!(relations(&linked), relations(&bits));
+        assert!(bits.has_precedence(&128, &64));
+
+        let bits = create::<BitGraph<Node>>(70).into_directed().unwrap();
+        let linked = create::<LinkedGraph<Node>>(70).into_directed().unwrap();
+        assert!(!bits.has_disjunctions());
+        assert_eq!(linked.critical_length().ok(), bits.critical_length().ok());
+    }
+}
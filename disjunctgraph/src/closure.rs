@@ -0,0 +1,82 @@
+use crate::{ Graph, GraphError, NodeId };
+
+/// Transitive closure of the precedences: one bitset row per node of all nodes it reaches.
+/// Fixing a disjunction through the closure updates the rows of every node that reaches its start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reachability {
+    /// Words per row
+    words: usize,
+    rows: Vec<u64>,
+}
+
+impl Reachability {
+    pub fn new<G: Graph>(graph: &G) -> Result<Self, GraphError> {
+        if let Some(cycle) = graph.find_cycle() {
+            return Err(GraphError::Cyclic(cycle));
+        }
+
+        let words = graph.nodes().len().div_ceil(64);
+        let mut closure = Reachability { words, rows: vec!(0; graph.nodes().len() * words) };
+
+        // Successors are done before their predecessors
+        for node in graph.topology_reverse().map(|n| n.id()).collect::<Vec<_>>() {
+            for successor in graph.successors(&node) {
+                closure.extend(node, successor.id());
+            }
+        }
+
+        Ok(closure)
+    }
+
+    /// There is a path of precedences node_1 -> ... -> node_2
+    pub fn reaches(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool {
+        let node_2 = node_2.id();
+        self.rows[node_1.id() * self.words + node_2 / 64] & (1 << (node_2 % 64)) != 0
+    }
+
+    /// Fix node_1 -> node_2 in the graph and in the closure.
+    pub fn fix_disjunction<G: Graph>(&mut self, graph: &mut G, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<(), GraphError> {
+        graph.fix_disjunction(node_1, node_2)?;
+
+        let (node_1, node_2) = (node_1.id(), node_2.id());
+        if !self.reaches(&node_1, &node_2) {
+            for node in 0..graph.nodes().len() {
+                if node == node_1 || self.reaches(&node, &node_1) {
+                    self.extend(node, node_2);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Disjunctions node_1 - node_2 of which the order is implied by a path node_1 -> ... -> node_2
+    pub fn implied<G: Graph>(&self, graph: &G) -> Vec<(usize, usize)> {
+        graph.nodes().iter()
+            .flat_map(|node| graph.disjunctions(node).map(move |other| (node.id(), other.id())))
+            .filter(|(node_1, node_2)| self.reaches(node_1, node_2))
+            .collect()
+    }
+
+    /// Fix all implied disjunctions, returns how many were fixed. The closure does not change.
+    pub fn remove_implied<G: Graph>(&self, graph: &mut G) -> Result<usize, GraphError> {
+        let implied = self.implied(graph);
+        for (node_1, node_2) in &implied {
+            graph.fix_disjunction(node_1, node_2)?;
+        }
+        Ok(implied.len())
+    }
+
+    /// Node reaches other and everything other reaches
+    fn extend(&mut self, node: usize, other: usize) {
+        for word in 0..self.words {
+            self.rows[node * self.words + word] |= self.rows[other * self.words + word];
+        }
+        self.rows[node * self.words + other / 64] |= 1 << (other % 64);
+    }
+}
+
+/// Fix all disjunctions that are implied by a path of precedences, returns how many were fixed.
+pub fn remove_implied<G: Graph>(graph: &mut G) -> Result<usize, GraphError> {
+    Reachability::new(graph)?.remove_implied(graph)
+}
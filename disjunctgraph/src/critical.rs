@@ -0,0 +1,195 @@
+use crate::{ Graph, GraphError, GraphNode, NodeId };
+
+/// Heads, tails and float of every node, the critical operations are those without float.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CriticalAnalysis {
+    pub makespan: u32,
+    /// Longest path from the source to the start of a node
+    pub heads: Vec<u32>,
+    /// Longest path from the completion of a node to the sink
+    pub tails: Vec<u32>,
+    /// How much a node can be delayed without delaying the makespan
+    pub total_float: Vec<u32>,
+    /// How much a node can be delayed without delaying any of its successors
+    pub free_float: Vec<u32>,
+}
+
+impl CriticalAnalysis {
+    pub fn new<G: Graph>(graph: &G) -> Result<Self, GraphError> {
+        if let Some(cycle) = graph.find_cycle() {
+            return Err(GraphError::Cyclic(cycle));
+        }
+
+        let heads = heads(graph);
+        let tails = tails(graph);
+        let makespan = heads[graph.sink().id()];
+
+        let completion = |node: &G::Node| heads[node.id()] + node.weight();
+        let total_float = graph.nodes().iter()
+            .map(|node| makespan - completion(node) - tails[node.id()])
+            .collect();
+        let free_float = graph.nodes().iter()
+            .map(|node| graph.successors(node).map(|s| heads[s.id()]).min().unwrap_or(makespan) - completion(node))
+            .collect();
+
+        Ok(CriticalAnalysis { makespan, heads, tails, total_float, free_float })
+    }
+
+    pub fn is_critical(&self, node: &impl NodeId) -> bool {
+        self.total_float[node.id()] == 0
+    }
+
+    /// Operations on a longest path, without the source and the sink
+    pub fn critical_nodes<G: Graph>(&self, graph: &G) -> Vec<usize> {
+        graph.nodes().iter()
+            .map(|node| node.id())
+            .filter(|id| *id != graph.source().id() && *id != graph.sink().id() && self.is_critical(id))
+            .collect()
+    }
+
+    /// Node_2 starts when critical node_1 completes
+    fn is_critical_arc<G: Graph>(&self, graph: &G, node_1: usize, node_2: usize) -> bool {
+        self.is_critical(&node_1) && self.is_critical(&node_2)
+            && self.heads[node_1] + graph[node_1].weight() == self.heads[node_2]
+    }
+
+    /// Every longest path from the source to the sink, without the source and the sink.
+    /// The number of paths can grow exponentially with the number of critical operations.
+    pub fn critical_paths<G: Graph>(&self, graph: &G) -> Vec<Vec<usize>> {
+        let (source, sink) = (graph.source().id(), graph.sink().id());
+        let successors = |node: usize| graph.successors(&node)
+            .map(|s| s.id())
+            .filter(|s| self.is_critical_arc(graph, node, *s))
+            .collect::<Vec<_>>();
+
+        let mut paths = Vec::new();
+        let mut path = Vec::new();
+        let mut stack = vec!(successors(source));
+        while let Some(next) = stack.last_mut().map(|s| s.pop()) {
+            match next {
+                Some(node) if node == sink => paths.push(path.clone()),
+                Some(node) => {
+                    path.push(node);
+                    stack.push(successors(node));
+                },
+                None => {
+                    stack.pop();
+                    path.pop();
+                }
+            }
+        }
+
+        paths
+    }
+
+    /// Maximal sequences of critical operations on one machine, every operation starting
+    /// when the one before completes. Only blocks of more than one operation are returned.
+    pub fn blocks<G: Graph>(&self, graph: &G) -> Vec<Vec<usize>> {
+        let mut machines: Vec<Vec<usize>> = Vec::new();
+        for id in self.critical_nodes(graph) {
+            if let Some(machine) = graph[id].machine_id() {
+                let machine = machine as usize;
+                if machines.len() <= machine {
+                    machines.resize(machine + 1, Vec::new());
+                }
+                machines[machine].push(id);
+            }
+        }
+
+        let mut blocks = Vec::new();
+        for mut operations in machines {
+            operations.sort_by_key(|id| self.heads[*id]);
+
+            let mut block: Vec<usize> = Vec::new();
+            for id in operations {
+                match block.last() {
+                    Some(&last) if graph.has_precedence(&last, &id) && self.is_critical_arc(graph, last, id) => block.push(id),
+                    _ => {
+                        if block.len() > 1 {
+                            blocks.push(block);
+                        }
+                        block = vec!(id);
+                    }
+                }
+            }
+            if block.len() > 1 {
+                blocks.push(block);
+            }
+        }
+
+        blocks
+    }
+}
+
+/// Longest path from the source to the start of every node
+pub(crate) fn heads<G: Graph>(graph: &G) -> Vec<u32> {
+    let mut heads = vec!(0; graph.nodes().len());
+    for node in graph.topology() {
+        heads[node.id()] = graph.predecessors(node).map(|x| heads[x.id()] + x.weight()).max().unwrap_or(0);
+    }
+    heads
+}
+
+/// Longest path from the completion of every node to the sink
+pub(crate) fn tails<G: Graph>(graph: &G) -> Vec<u32> {
+    let mut tails = vec!(0; graph.nodes().len());
+    for node in graph.topology_reverse() {
+        tails[node.id()] = graph.successors(node).map(|x| tails[x.id()] + x.weight()).max().unwrap_or(0);
+    }
+    tails
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ LinkedGraph, Relation };
+
+    #[derive(Clone, Debug)]
+    struct Node(usize, u32, Option<u32>);
+
+    impl NodeId for Node {
+        fn id(&self) -> usize { self.0 }
+    }
+
+    impl GraphNode for Node {
+        fn create(id: usize, weight: u32, machine_id: Option<u32>, _: Option<usize>) -> Self { Node(id, weight, machine_id) }
+        fn weight(&self) -> u32 { self.1 }
+        fn job_id(&self) -> Option<usize> { None }
+        fn machine_id(&self) -> Option<u32> { self.2 }
+    }
+
+    /// Jobs 1 -> 2 and 3 -> 4, machine 1 processes 1 then 3 and machine 2 processes 2 then 4
+    fn create(weight_2: u32) -> LinkedGraph<Node> {
+        use Relation::*;
+        let nodes = vec!(Node(0, 0, None), Node(1, 2, Some(1)), Node(2, weight_2, Some(2)), Node(3, 2, Some(1)), Node(4, 3, Some(2)), Node(5, 0, None));
+        let edges = vec!(
+            vec!(Successor(1), Successor(3)),
+            vec!(Predecessor(0), Successor(2), Successor(3)),
+            vec!(Predecessor(1), Successor(4), Successor(5)),
+            vec!(Predecessor(0), Predecessor(1), Successor(4)),
+            vec!(Predecessor(2), Predecessor(3), Successor(5)),
+            vec!(Predecessor(2), Predecessor(4)));
+        LinkedGraph::create(nodes, edges)
+    }
+
+    #[test]
+    fn test_critical_analysis() {
+        let graph = create(1);
+        let analysis = CriticalAnalysis::new(&graph).unwrap();
+        assert_eq!(7, analysis.makespan);
+        assert_eq!(vec!(0, 0, 2, 2, 4, 7), analysis.heads);
+        assert_eq!(vec!(7, 5, 3, 3, 0, 0), analysis.tails);
+        assert_eq!(vec!(1, 3, 4), analysis.critical_nodes(&graph));
+        assert_eq!((1, 1), (analysis.total_float[2], analysis.free_float[2]));
+        assert_eq!(vec!(vec!(1, 3, 4)), analysis.critical_paths(&graph));
+        assert_eq!(vec!(vec!(1, 3)), analysis.blocks(&graph));
+
+        let graph = create(2);
+        let analysis = CriticalAnalysis::new(&graph).unwrap();
+        let mut paths = analysis.critical_paths(&graph);
+        paths.sort();
+        assert_eq!(vec!(vec!(1, 2, 4), vec!(1, 3, 4)), paths);
+        assert_eq!(vec!(vec!(1, 3), vec!(2, 4)), analysis.blocks(&graph));
+        assert!(analysis.free_float.iter().all(|float| *float == 0));
+    }
+}
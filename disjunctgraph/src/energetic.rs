@@ -0,0 +1,107 @@
+//! Energetic reasoning on the head/tail windows of a resource.
+//!
+//! For an interval [t1, t2] every operation has a mandatory part that is processed within
+//! the interval, whether it is left-shifted (started at its head) or right-shifted (started at its lst):
+//! W(i, t1, t2) = min(t2 - t1, p(i), max(0, ect(i) - t1), max(0, t2 - lst(i)))
+//!
+//! If the mandatory parts of all operations exceed t2 - t1 the resource is infeasible.
+//! If the left-shifted part of i, p+(i) = min(p(i), max(0, ect(i) - t1)), does not fit
+//! next to the mandatory parts of the others, then i has to end after t2 and only uses
+//! the remaining capacity at the end of the interval: head(i) >= t2 - capacity.
+//! The right-shifted part p-(i) = min(p(i), max(0, t2 - lst(i))) gives lct(i) <= t1 + capacity.
+//!
+//! Unlike task intervals this counts operations that only partially overlap the interval.
+use crate::{ ConstrainedNode, Graph, GraphNode, GraphError, Deductions };
+use itertools::Itertools;
+
+/// Search the operations on every resource for head and tail adjustments by energetic reasoning.
+/// The graph is not changed, the deductions contain no orders.
+pub fn energetic_reasoning<T: Graph>(resources: &[u32], graph: &T, upper_bound: u32) -> Result<Deductions, GraphError> where T::Node: ConstrainedNode {
+
+    let mut deductions = Deductions::default();
+
+    for resource in resources {
+        let resource = graph.nodes().iter()
+            .filter(|n| n.machine_id() == Some(*resource))
+            .map(|n| Window::new(n, upper_bound))
+            .collect_vec();
+
+        resource_energy(&resource, &mut deductions)?;
+    }
+
+    Ok(deductions)
+}
+
+/// Time window of an operation, signed to keep the arithmetic simple.
+struct Window {
+    id: usize,
+    est: i64,
+    lst: i64,
+    p: i64,
+    tail: i64,
+}
+
+impl Window {
+    fn new<N: ConstrainedNode>(node: &N, upper_bound: u32) -> Self {
+        Window {
+            id: node.id(),
+            est: node.head() as i64,
+            // Not node.lst, the tail can exceed an upper bound that was lowered since it was propagated.
+            lst: upper_bound as i64 - node.tail() as i64 - node.weight() as i64,
+            p: node.weight() as i64,
+            tail: node.tail() as i64,
+        }
+    }
+
+    fn ect(&self) -> i64 { self.est + self.p }
+    fn lct(&self) -> i64 { self.lst + self.p }
+
+    fn left_shifted(&self, t1: i64, t2: i64) -> i64 {
+        (t2 - t1).min(self.p).min((self.ect() - t1).max(0))
+    }
+
+    fn right_shifted(&self, t1: i64, t2: i64) -> i64 {
+        (t2 - t1).min(self.p).min((t2 - self.lst).max(0))
+    }
+
+    fn mandatory(&self, t1: i64, t2: i64) -> i64 {
+        self.left_shifted(t1, t2).min(self.right_shifted(t1, t2))
+    }
+}
+
+fn resource_energy(resource: &[Window], deductions: &mut Deductions) -> Result<(), GraphError> {
+    // An operation that does not fit within the upper bound on its own.
+    if resource.iter().any(|w| w.lst < w.est) {
+        return Err(GraphError::Infeasible);
+    }
+
+    // Only intervals starting at an est, lst or ect and ending at an lct, ect or lst are relevant.
+    let starts = resource.iter().flat_map(|w| vec!(w.est, w.lst, w.ect())).unique().collect_vec();
+    let ends = resource.iter().flat_map(|w| vec!(w.lct(), w.ect(), w.lst)).unique().collect_vec();
+
+    for (&t1, &t2) in starts.iter().cartesian_product(ends.iter()) {
+        if t1 >= t2 {
+            continue;
+        }
+
+        let energy: i64 = resource.iter().map(|w| w.mandatory(t1, t2)).sum();
+        if energy > t2 - t1 {
+            return Err(GraphError::Infeasible);
+        }
+
+        for w in resource {
+            let capacity = t2 - t1 - (energy - w.mandatory(t1, t2));
+
+            if w.left_shifted(t1, t2) > capacity && t2 - capacity > w.est {
+                deductions.heads.push((w.id, (t2 - capacity) as u32));
+            }
+
+            if w.right_shifted(t1, t2) > capacity && t1 + capacity < w.lct() {
+                // lct(i) <= t1 + capacity, the tail grows by as much as the lct shrinks
+                deductions.tails.push((w.id, (w.tail + w.lct() - (t1 + capacity)) as u32));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,15 +1,31 @@
 mod linked_graph;
+mod matrix_graph;
+mod bit_graph;
+mod closure;
+mod critical;
 mod utils;
+mod energetic;
+mod order;
+pub mod transposition;
 
 use itertools::Itertools;
 use std::collections::VecDeque;
 
 pub use linked_graph::LinkedGraph;
+pub use matrix_graph::MatrixGraph;
+pub use bit_graph::BitGraph;
+pub use closure::{ Reachability, remove_implied };
+pub use critical::CriticalAnalysis;
+pub use utils::{ apply_windows, find_orders, Deductions };
+pub use energetic::energetic_reasoning;
 
 
 #[derive(Debug)]
 pub enum GraphError {
-    Cyclic, InvalidEdge
+    /// Node ids of a cycle, every node precedes the next and the last node precedes the first.
+    Cyclic(Vec<usize>),
+    InvalidEdge,
+    Infeasible
 }
 
 pub trait NodeId {
@@ -67,13 +83,32 @@ pub enum Relation {
 }
 
 
-pub struct NodeIterator<'a, G: Graph>(Box<dyn Iterator<Item = &'a G::Node> + 'a>);
+/// Related nodes of a node. Graphs that can walk their relations by index do so without allocating.
+pub struct NodeIterator<'a, G: Graph>(Nodes<'a, G>);
+
+enum Nodes<'a, G: Graph> {
+    Boxed(Box<dyn Iterator<Item = &'a G::Node> + 'a>),
+    Bits(&'a [G::Node], bit_graph::Bits<'a>),
+}
+
+impl<'a, G: Graph> NodeIterator<'a, G> {
+    fn boxed(iter: impl Iterator<Item = &'a G::Node> + 'a) -> Self {
+        NodeIterator(Nodes::Boxed(Box::new(iter)))
+    }
+
+    fn bits(nodes: &'a [G::Node], bits: bit_graph::Bits<'a>) -> Self {
+        NodeIterator(Nodes::Bits(nodes, bits))
+    }
+}
 
 impl<'a, G:Graph> Iterator for NodeIterator<'a, G> {
     type Item = &'a G::Node;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.next()
+        match &mut self.0 {
+            Nodes::Boxed(iter) => iter.next(),
+            Nodes::Bits(nodes, bits) => bits.next().map(|id| &nodes[id])
+        }
     }
 }
 
@@ -89,6 +124,9 @@ pub trait Graph where Self: Sized + std::ops::IndexMut<usize, Output = <Self as
     fn predecessors(&self, id: &impl NodeId) -> NodeIterator<Self>;
     fn disjunctions(&self, id: &impl NodeId) -> NodeIterator<Self>;
     fn fix_disjunction(&mut self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<(), GraphError>;    
+    /// Turn the fixed disjunction node_1 -> node_2 back into a disjunction.
+    /// Precedences of the jobs were never disjunctions and can not be unfixed.
+    fn unfix_disjunction(&mut self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<(), GraphError>;
     fn flip_edge(self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<Self, GraphError>;
     fn into_directed(&self) -> Result<Self, GraphError>;    
 
@@ -99,9 +137,18 @@ pub trait Graph where Self: Sized + std::ops::IndexMut<usize, Output = <Self as
     /// Graph contains relation: node_1 -> node_2
     fn has_precedence(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool;
     fn has_disjunction(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool;
+    /// Graph contains relation node_1 -> node_2 and it was a disjunction that has been fixed
+    fn has_fixed_disjunction(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool;
 
     fn node_has_disjunction(&self, node: &impl NodeId) -> bool;
 
+    /// Zobrist hash of all precedences in the graph, identifies the selection made so far.
+    fn selection_hash(&self) -> u64 {
+        self.nodes().iter()
+            .flat_map(|node| self.successors(node).map(move |s| transposition::arc_key(node.id(), s.id())))
+            .fold(0, |hash, key| hash ^ key)
+    }
+
     /// Retrieves topology ordering in the graph, starting at the source, ending at the sink.
     fn topology<'a>(&'a self) -> TopologyIterator<'a, Self> {
 
@@ -151,7 +198,7 @@ pub trait Graph where Self: Sized + std::ops::IndexMut<usize, Output = <Self as
             
             /*let any_predecessor_smaller = predecessors.iter().any(|x| topology[x.id()] < topology[node] );                        
             if any_predecessor_smaller {
-                return Err(GraphError::Cyclic);
+                return Err(GraphError::Cyclic(Vec::new()));
             }*/
 
             let nodes = self.nodes();   
@@ -168,7 +215,7 @@ pub trait Graph where Self: Sized + std::ops::IndexMut<usize, Output = <Self as
         let max_span = starting_times.last().unwrap();
         let mut path = Vec::new();
         let mut pointer = backtracker[self.sink().id()];
-        while pointer != 0 {
+        while pointer != self.source().id() {
             let prev = backtracker[pointer];
             let node = &self[pointer];
             path.push(node);
@@ -178,6 +225,79 @@ pub trait Graph where Self: Sized + std::ops::IndexMut<usize, Output = <Self as
         Ok((*max_span, path))
     }
 
+    /// Node ids of a path node_1 -> ... -> node_2 over precedences, None if node_2 is not reachable.
+    /// Graphs that keep a topological order only search the nodes ordered between the two.
+    fn precedence_path(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> Option<Vec<usize>> {
+        let (from, to) = (node_1.id(), node_2.id());
+        let mut parent = vec!(usize::MAX; self.nodes().len());
+        let mut stack = vec!(from);
+        parent[from] = from;
+
+        while let Some(node) = stack.pop() {
+            if node == to {
+                let mut path = vec!(to);
+                while *path.last().unwrap() != from {
+                    path.push(parent[*path.last().unwrap()]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for successor in self.successors(&node) {
+                if parent[successor.id()] == usize::MAX {
+                    parent[successor.id()] = node;
+                    stack.push(successor.id());
+                }
+            }
+        }
+
+        None
+    }
+
+    /// There is a path of precedences node_1 -> ... -> node_2.
+    /// Searches the graph on every call, `Reachability` answers repeated queries.
+    fn reaches(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool {
+        self.precedence_path(node_1, node_2).is_some()
+    }
+
+    /// Node ids of a cycle in the precedences, in the order of `GraphError::Cyclic`.
+    fn find_cycle(&self) -> Option<Vec<usize>> {
+        // Depth first search, a successor that is still on the stack closes a cycle.
+        const UNVISITED: u8 = 0;
+        const ON_STACK: u8 = 1;
+        const DONE: u8 = 2;
+        let mut state = vec!(UNVISITED; self.nodes().len());
+
+        for start in 0..self.nodes().len() {
+            if state[start] != UNVISITED {
+                continue;
+            }
+
+            state[start] = ON_STACK;
+            let mut stack = vec!((start, self.successors(&start).map(|n| n.id()).collect_vec()));
+            while !stack.is_empty() {
+                let top = stack.len() - 1;
+                match stack[top].1.pop() {
+                    Some(next) if state[next] == UNVISITED => {
+                        state[next] = ON_STACK;
+                        stack.push((next, self.successors(&next).map(|n| n.id()).collect_vec()));
+                    },
+                    Some(next) if state[next] == ON_STACK => {
+                        let position = stack.iter().position(|(node, _)| *node == next).unwrap();
+                        return Some(stack[position..].iter().map(|(node, _)| *node).collect());
+                    },
+                    Some(_) => (),
+                    None => {
+                        state[stack[top].0] = DONE;
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
     fn is_cyclic(&self) -> bool {
         
         // Start DFS from source        
@@ -204,14 +324,10 @@ pub trait Graph where Self: Sized + std::ops::IndexMut<usize, Output = <Self as
 
     fn init_weights(&mut self)
     where Self::Node: ConstrainedNode {
-        for node in self.topology().map(|n| n.id()).collect_vec() {
-            let head = self.predecessors(&node).map(|x| x.head() + x.weight()).max().unwrap_or(0);
-            self[node].set_head(head);
-        }
-
-        for node in self.topology_reverse().map(|n| n.id()).collect_vec() {
-            let tail = self.successors(&node).map(|x| x.tail() + x.weight()).max().unwrap_or(0);
-            self[node].set_tail(tail);
+        let (heads, tails) = (critical::heads(self), critical::tails(self));
+        for (node, (head, tail)) in self.nodes_mut().iter_mut().zip(heads.into_iter().zip(tails)) {
+            node.set_head(head);
+            node.set_tail(tail);
         }
 
         debug_assert!(self.nodes().iter().all(|node|{
@@ -330,4 +446,163 @@ impl<'a, G: Graph> Iterator for ReverseTopologyIterator<'a, G> {
             }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct Node(usize);
+
+    impl NodeId for Node {
+        fn id(&self) -> usize { self.0 }
+    }
+
+    impl GraphNode for Node {
+        fn create(id: usize, _: u32, _: Option<u32>, _: Option<usize>) -> Self { Node(id) }
+        fn weight(&self) -> u32 { 1 }
+        fn job_id(&self) -> Option<usize> { None }
+        fn machine_id(&self) -> Option<u32> { None }
+    }
+
+    /// 0 -> 1 -> 2 -> 3 -> 4 with 1 - 3 disjunctive
+    fn chain<G: Graph<Node = Node>>() -> G {
+        use Relation::*;
+        let edges = vec!(
+            vec!(Successor(1)),
+            vec!(Predecessor(0), Successor(2), Disjunctive(3)),
+            vec!(Predecessor(1), Successor(3)),
+            vec!(Predecessor(2), Successor(4), Disjunctive(1)),
+            vec!(Predecessor(3)));
+        G::create((0..5).map(Node).collect(), edges)
+    }
+
+    fn cycles<G: Graph<Node = Node>>() {
+        let mut graph: G = chain();
+        match graph.fix_disjunction(&3, &1) {
+            Err(GraphError::Cyclic(cycle)) => assert_eq!(vec!(1, 2, 3), cycle),
+            other => panic!("Expected a cycle, got {:?}", other)
+        }
+        // The graph is left as it was
+        assert!(graph.has_disjunction(&3, &1));
+        assert_eq!(None, graph.find_cycle());
+
+        graph.fix_disjunction(&1, &3).unwrap();
+        match graph.flip_edge(&1, &3) {
+            Err(GraphError::Cyclic(cycle)) => assert_eq!(vec!(1, 2, 3), cycle),
+            other => panic!("Expected a cycle, got {:?}", other.map(|_| ()))
+        }
+
+        let graph: G = chain();
+        let graph = graph.flip_edge(&3, &4).unwrap();
+        assert_eq!(Some(vec!(0, 1, 2, 3)), graph.precedence_path(&0, &3));
+        assert_eq!(None, graph.precedence_path(&3, &4));
+
+        // Fixing against the topological order of the graph moves the nodes in between
+        for order in &[[1, 2, 3], [3, 2, 1]] {
+            let mut graph: G = machine();
+            graph.fix_disjunction(&order[0], &order[1]).unwrap();
+            graph.fix_disjunction(&order[1], &order[2]).unwrap();
+            assert_eq!(Some(order.to_vec()), graph.precedence_path(&order[0], &order[2]));
+            match graph.fix_disjunction(&order[2], &order[0]) {
+                Err(GraphError::Cyclic(cycle)) => assert_eq!(order.to_vec(), cycle),
+                other => panic!("Expected a cycle, got {:?}", other)
+            }
+            let graph = graph.flip_edge(&order[0], &order[1]).unwrap();
+            assert_eq!(Some(vec!(order[1], order[0])), graph.precedence_path(&order[1], &order[0]));
+            assert_eq!(None, graph.precedence_path(&order[0], &order[1]));
+        }
+    }
+
+    /// 0 -> 1, 2, 3 -> 4 with 1, 2 and 3 on one machine
+    fn machine<G: Graph<Node = Node>>() -> G {
+        use Relation::*;
+        let edges = vec!(
+            vec!(Successor(1), Successor(2), Successor(3)),
+            vec!(Predecessor(0), Successor(4), Disjunctive(2), Disjunctive(3)),
+            vec!(Predecessor(0), Successor(4), Disjunctive(1), Disjunctive(3)),
+            vec!(Predecessor(0), Successor(4), Disjunctive(1), Disjunctive(2)),
+            vec!(Predecessor(1), Predecessor(2), Predecessor(3)));
+        G::create((0..5).map(Node).collect(), edges)
+    }
+
+    #[test]
+    fn test_reachability() {
+        let mut graph: LinkedGraph<Node> = machine();
+        let mut closure = Reachability::new(&graph).unwrap();
+        assert!(closure.reaches(&0, &4));
+        assert!(!closure.reaches(&1, &3));
+
+        closure.fix_disjunction(&mut graph, &1, &2).unwrap();
+        closure.fix_disjunction(&mut graph, &2, &3).unwrap();
+        assert!(closure.reaches(&1, &3) && graph.reaches(&1, &3));
+        assert!(!graph.has_precedence(&1, &3));
+        assert_eq!(Reachability::new(&graph).unwrap(), closure);
+
+        assert_eq!(vec!((1, 3)), closure.implied(&graph));
+        assert_eq!(1, remove_implied(&mut graph).unwrap());
+        assert!(graph.has_precedence(&1, &3));
+        assert!(!graph.has_disjunctions());
+    }
+
+    fn unfix<G: Graph<Node = Node>>() {
+        let mut graph: G = chain();
+        let hash = graph.selection_hash();
+        graph.fix_disjunction(&1, &3).unwrap();
+        assert!(graph.has_fixed_disjunction(&1, &3));
+
+        // Job precedences stay
+        assert!(!graph.has_fixed_disjunction(&1, &2));
+        assert!(graph.unfix_disjunction(&1, &2).is_err());
+        assert!(graph.unfix_disjunction(&3, &1).is_err());
+
+        graph.unfix_disjunction(&1, &3).unwrap();
+        assert!(graph.has_disjunction(&1, &3) && graph.has_disjunction(&3, &1));
+        assert!(!graph.has_precedence(&1, &3));
+        assert_eq!(hash, graph.selection_hash());
+
+        // A flipped disjunction is still fixed
+        let mut graph: G = machine();
+        graph.fix_disjunction(&1, &2).unwrap();
+        let mut graph = graph.flip_edge(&1, &2).unwrap();
+        assert!(graph.has_fixed_disjunction(&2, &1) && !graph.has_fixed_disjunction(&1, &2));
+        graph.unfix_disjunction(&2, &1).unwrap();
+        assert!(graph.has_disjunction(&1, &2));
+
+        let mut graph = graph.into_directed().unwrap();
+        graph.unfix_disjunction(&1, &3).unwrap();
+        assert!(graph.has_disjunction(&1, &3));
+        assert!(graph.unfix_disjunction(&0, &1).is_err());
+    }
+
+    #[test]
+    fn test_unfix() {
+        unfix::<LinkedGraph<Node>>();
+        unfix::<MatrixGraph<Node>>();
+        unfix::<BitGraph<Node>>();
+    }
+
+    #[test]
+    fn test_cycles() {
+        cycles::<LinkedGraph<Node>>();
+        cycles::<MatrixGraph<Node>>();
+        cycles::<BitGraph<Node>>();
+    }
+
+    #[test]
+    fn test_selection_hash() {
+        let mut graph: LinkedGraph<Node> = machine();
+        assert_eq!(graph.rehash(), graph.selection_hash());
+        graph.fix_disjunction(&1, &2).unwrap();
+        graph.fix_disjunction(&2, &3).unwrap();
+        assert_eq!(graph.rehash(), graph.selection_hash());
+
+        let mut graph = graph.flip_edge(&1, &2).unwrap();
+        assert_eq!(graph.rehash(), graph.selection_hash());
+        graph.unfix_disjunction(&2, &3).unwrap();
+        assert_eq!(graph.rehash(), graph.selection_hash());
+
+        let graph = graph.into_directed().unwrap();
+        assert_eq!(graph.rehash(), graph.selection_hash());
+    }
+}
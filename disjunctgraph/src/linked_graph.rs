@@ -1,13 +1,20 @@
 use hashbrown::HashSet;
 use itertools::Itertools;
 use crate::{ NodeId, GraphNode, ConstrainedNode, NodeIterator, Graph, Relation, GraphError, self as disjunctgraph };
+use crate::transposition::arc_key;
+use crate::order::TopologicalOrder;
 
 #[derive(Clone)]
 pub struct LinkedGraph<T: NodeId + Clone> {
     nodes: Vec<T>,
     successors: Vec<HashSet<usize>>,
     predecessors: Vec<HashSet<usize>>,
-    disjunctions: Vec<HashSet<usize>>
+    disjunctions: Vec<HashSet<usize>>,
+    /// Successors that were disjunctions, the others are precedences of the jobs
+    fixed: Vec<HashSet<usize>>,
+    hash: u64,
+    /// Topological order of the precedences, to keep cycle checks local
+    order: TopologicalOrder
 }
 
 impl<T: NodeId + GraphNode + Clone> Graph for LinkedGraph<T> {
@@ -39,12 +46,18 @@ impl<T: NodeId + GraphNode + Clone> Graph for LinkedGraph<T> {
                     }
             }).collect::<HashSet<_>>());
         
-        LinkedGraph {
+        let mut graph = LinkedGraph {
+            fixed: vec!(HashSet::new(); nodes.len()),
             nodes,
             successors: successors.collect(),
             predecessors: predecessors.collect(),
-            disjunctions: disjunctions.collect()
-        }
+            disjunctions: disjunctions.collect(),
+            hash: 0,
+            order: TopologicalOrder::default()
+        };
+        graph.hash = graph.rehash();
+        graph.order = TopologicalOrder::new(&graph);
+        graph
     }
 
     fn nodes(&self) -> &[T] {
@@ -64,16 +77,16 @@ impl<T: NodeId + GraphNode + Clone> Graph for LinkedGraph<T> {
 	}
     
     fn successors(&self, id: &impl NodeId) -> NodeIterator<Self> {
-        NodeIterator(Box::new(self.successors[id.id()].iter().map(move |x| &self.nodes[*x])))
+        NodeIterator::boxed(self.successors[id.id()].iter().map(move |x| &self.nodes[*x]))
     }
 
 
     fn predecessors<'a>(&'a self, id: &impl NodeId) -> NodeIterator<Self> {
-		NodeIterator(Box::new(self.predecessors[id.id()].iter().map(move |x| &self.nodes[*x])))
+		NodeIterator::boxed(self.predecessors[id.id()].iter().map(move |x| &self.nodes[*x]))
 	}
 
     fn disjunctions<'a>(&'a self, id: &impl NodeId) -> NodeIterator<Self> {        
-		NodeIterator(Box::new(self.disjunctions[id.id()].iter().map(move |x| &self.nodes[*x])))
+		NodeIterator::boxed(self.disjunctions[id.id()].iter().map(move |x| &self.nodes[*x]))
 	}
 
     fn fix_disjunction(&mut self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<(), GraphError> {
@@ -83,6 +96,11 @@ impl<T: NodeId + GraphNode + Clone> Graph for LinkedGraph<T> {
 
         let node_1 = node_1.id();
         let node_2 = node_2.id();
+
+        // Node_2 already reaches node_1
+        if let Some(cycle) = self.precedence_path(&node_2, &node_1) {
+            return Err(GraphError::Cyclic(cycle));
+        }
         
         // Remove from disjunctions        
         self.disjunctions[node_1].remove(&node_2);
@@ -91,16 +109,34 @@ impl<T: NodeId + GraphNode + Clone> Graph for LinkedGraph<T> {
         // Node_1 -> Node_2
         self.successors[node_1].insert(node_2);
         self.predecessors[node_2].insert(node_1); 
+        self.fixed[node_1].insert(node_2);
+        self.hash ^= arc_key(node_1, node_2);
+
+        let affected = self.order.affected(self, node_1, node_2);
+        self.order.reorder(affected);
 
-        Ok(())       
-		
-        /*if self.is_cyclic() {
-            Err(disjunctgraph::GraphError::Cyclic)
-        } else {
-            Ok(())
-        }*/
+        Ok(())
 	}
 
+    fn unfix_disjunction(&mut self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<(), GraphError> {
+        if !self.fixed[node_1.id()].contains(&node_2.id()) {
+            return Err(GraphError::InvalidEdge);
+        }
+
+        let node_1 = node_1.id();
+        let node_2 = node_2.id();
+
+        self.fixed[node_1].remove(&node_2);
+        self.successors[node_1].remove(&node_2);
+        self.predecessors[node_2].remove(&node_1);
+        self.hash ^= arc_key(node_1, node_2);
+
+        self.disjunctions[node_1].insert(node_2);
+        self.disjunctions[node_2].insert(node_1);
+
+        Ok(())
+    }
+
     fn flip_edge(mut self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<Self, GraphError> {
         if !self.successors[node_1.id()].contains(&node_2.id()) {
             return Err(GraphError::InvalidEdge);
@@ -112,14 +148,21 @@ impl<T: NodeId + GraphNode + Clone> Graph for LinkedGraph<T> {
         self.successors[node_1].remove(&node_2);
         self.predecessors[node_2].remove(&node_1);
 
+        // Node_1 reaches node_2 in another way
+        if let Some(cycle) = self.precedence_path(&node_1, &node_2) {
+            return Err(GraphError::Cyclic(cycle));
+        }
+
         self.predecessors[node_1].insert(node_2);
         self.successors[node_2].insert(node_1);
-        		
-        // if self.is_cyclic() {
-        //     Err(disjunctgraph::GraphError::Cyclic)
-        // } else {
-        //     Ok(self)
-        // }
+        if self.fixed[node_1].remove(&node_2) {
+            self.fixed[node_2].insert(node_1);
+        }
+        self.hash ^= arc_key(node_1, node_2) ^ arc_key(node_2, node_1);
+
+        let affected = self.order.affected(&self, node_2, node_1);
+        self.order.reorder(affected);
+
         Ok(self)
 	}
 
@@ -136,14 +179,18 @@ impl<T: NodeId + GraphNode + Clone> Graph for LinkedGraph<T> {
 
                     cloned.successors[node_1].insert(node_2);
                     cloned.predecessors[node_2].insert(node_1);
+                    cloned.fixed[node_1].insert(node_2);
+                    cloned.hash ^= arc_key(node_1, node_2);
                 }
             }
         }
 
-        if cloned.is_cyclic() {
-            Err(disjunctgraph::GraphError::Cyclic)
-        } else {
-            Ok(cloned)
+        match cloned.find_cycle() {
+            Some(cycle) => Err(disjunctgraph::GraphError::Cyclic(cycle)),
+            None => {
+                cloned.order = TopologicalOrder::new(&cloned);
+                Ok(cloned)
+            }
         }
 	}
 
@@ -156,9 +203,21 @@ impl<T: NodeId + GraphNode + Clone> Graph for LinkedGraph<T> {
         self.disjunctions[node_1.id()].contains(&node_2.id())
     }
 
+    fn has_fixed_disjunction(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool {
+        self.fixed[node_1.id()].contains(&node_2.id())
+    }
+
     fn node_has_disjunction(&self, node: &impl NodeId) -> bool {
         !self.disjunctions[node.id()].is_empty()
     }    
+
+    fn precedence_path(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> Option<Vec<usize>> {
+        self.order.path(self, node_1.id(), node_2.id())
+    }
+
+    fn selection_hash(&self) -> u64 {
+        self.hash
+    }
 }
 
 
@@ -182,6 +241,13 @@ impl <T: NodeId + Clone> LinkedGraph<T> {
     pub fn total_disjunctions(&self) -> usize {
         self.disjunctions.iter().map(|n| n.len()).sum()
     }
+
+    /// The selection hash from scratch, the graph keeps it up to date while arcs change.
+    pub(crate) fn rehash(&self) -> u64 {
+        self.successors.iter().enumerate()
+            .flat_map(|(node, successors)| successors.iter().map(move |s| arc_key(node, *s)))
+            .fold(0, |hash, key| hash ^ key)
+    }
 }
 
 impl<T: ConstrainedNode + Clone> std::fmt::Debug for LinkedGraph<T> {
@@ -207,4 +273,96 @@ impl<T: ConstrainedNode + Clone> std::fmt::Debug for LinkedGraph<T> {
 
         write!(f, "")
     }
-}
\ No newline at end of file
+}
+
+/// Graphs are stored as their nodes and the relations of every node.
+/// Predecessors follow from the successors and are not stored.
+#[cfg(feature = "serialize")]
+mod serialize {
+    use super::LinkedGraph;
+    use crate::{ Graph, GraphError, GraphNode, NodeId, Relation };
+    use serde::{ Serialize, Serializer, Deserialize, Deserializer, de::Error };
+    use serde_derive::{ Serialize, Deserialize };
+    use hashbrown::HashSet;
+    use std::convert::TryFrom;
+
+    #[derive(Serialize, Deserialize)]
+    struct Snapshot<N> {
+        nodes: N,
+        successors: Vec<Vec<usize>>,
+        disjunctions: Vec<Vec<usize>>,
+        /// Successors that were disjunctions
+        fixed: Vec<Vec<usize>>,
+    }
+
+    fn sorted(sets: &[HashSet<usize>]) -> Vec<Vec<usize>> {
+        sets.iter().map(|set| { let mut ids = set.iter().cloned().collect::<Vec<_>>(); ids.sort(); ids }).collect()
+    }
+
+    impl<T: NodeId + Clone + Serialize> Serialize for LinkedGraph<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Snapshot {
+                nodes: &self.nodes,
+                successors: sorted(&self.successors),
+                disjunctions: sorted(&self.disjunctions),
+                fixed: sorted(&self.fixed),
+            }.serialize(serializer)
+        }
+    }
+
+    impl<'de, T: NodeId + GraphNode + Clone + Deserialize<'de>> Deserialize<'de> for LinkedGraph<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let snapshot: Snapshot<Vec<T>> = Snapshot::deserialize(deserializer)?;
+            LinkedGraph::try_from(snapshot).map_err(|e| D::Error::custom(format!("invalid graph: {:?}", e)))
+        }
+    }
+
+    /// Relations that refer to missing nodes, one sided disjunctions or fixed disjunctions
+    /// that are not precedences are an invalid edge, a cycle in the precedences is cyclic.
+    impl<T: NodeId + GraphNode + Clone> TryFrom<Snapshot<Vec<T>>> for LinkedGraph<T> {
+        type Error = GraphError;
+
+        fn try_from(snapshot: Snapshot<Vec<T>>) -> Result<Self, GraphError> {
+            let n = snapshot.nodes.len();
+
+            if snapshot.successors.len() != n || snapshot.disjunctions.len() != n || snapshot.fixed.len() != n {
+                return Err(GraphError::InvalidEdge);
+            }
+            if snapshot.nodes.iter().enumerate().any(|(i, node)| node.id() != i) {
+                return Err(GraphError::InvalidEdge);
+            }
+            if snapshot.successors.iter().chain(&snapshot.disjunctions).chain(&snapshot.fixed).flatten().any(|other| *other >= n) {
+                return Err(GraphError::InvalidEdge);
+            }
+            let symmetric = snapshot.disjunctions.iter().enumerate()
+                .all(|(node, disjunctions)| disjunctions.iter().all(|d| *d != node && snapshot.disjunctions[*d].contains(&node)));
+            if !symmetric {
+                return Err(GraphError::InvalidEdge);
+            }
+
+            let mut edges = vec!(Vec::new(); n);
+            for (node, successors) in snapshot.successors.iter().enumerate() {
+                for successor in successors.iter().cloned() {
+                    edges[node].push(Relation::Successor(successor));
+                    edges[successor].push(Relation::Predecessor(node));
+                }
+            }
+            for (node, disjunctions) in snapshot.disjunctions.iter().enumerate() {
+                edges[node].extend(disjunctions.iter().map(|d| Relation::Disjunctive(*d)));
+            }
+
+            let mut graph = LinkedGraph::create(snapshot.nodes, edges);
+            for (node, fixed) in snapshot.fixed.into_iter().enumerate() {
+                if fixed.iter().any(|f| !graph.has_precedence(&node, f)) {
+                    return Err(GraphError::InvalidEdge);
+                }
+                graph.fixed[node] = fixed.into_iter().collect();
+            }
+
+            match graph.find_cycle() {
+                Some(cycle) => Err(GraphError::Cyclic(cycle)),
+                None => Ok(graph)
+            }
+        }
+    }
+}
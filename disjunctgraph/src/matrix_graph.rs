@@ -1,113 +1,400 @@
-//use crate::{ NodeId, GraphNode, Graph, self as disjunctgraph };
+use crate::{ NodeId, GraphNode, ConstrainedNode, NodeIterator, Graph, Relation, GraphError, self as disjunctgraph };
+use crate::transposition::arc_key;
+use crate::order::TopologicalOrder;
 
-// This will be an N x (N + 3) matrix. (N includes source and sink)
+// This is an N x (N + 3) matrix. (N includes source and sink)
 // As an adjustment of this:
 // https://ac.els-cdn.com/S0377221799004865/1-s2.0-S0377221799004865-main.pdf?_tid=bc7e7478-9c03-4eee-b93e-4d793c0aca48&acdnat=1552300381_a73c9971ebc05ade30ef09824ed52d9f
-// A cell ranges from: -2n to 2n
-// In the first matrix from columns 0 to n
-// n  <  x <= 2n => Successor and (x-n) is the next successor
-// 0  <= x <= n  => Predecessor and x is the next predecessor
-// -n <= x < 0   => Disjunctive relationship and -x is the next disjunction
-// -2n <= x < -n => No relationship and (x + n) is the next unkown relationship
-
-// Node 0 is the source
-// Node (n-1) is the sink
-// Column (n+1) references to the first successor
-// Column (n+2) references to the first predecessor
-// Column (n+3) references to the first disjunction
-/*
+// Row i describes the relations of node i, cell (i, j) is part of a circular doubly linked list
+// of the successors, predecessors or disjunctions of i, depending on the relation between i and j.
+// Column n is the head of the successors
+// Column (n+1) is the head of the predecessors
+// Column (n+2) is the head of the disjunctions
+// An empty list points to its own head.
+// Relations are looked up in O(1), iterating a list only visits its members.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Unrelated, Successor, Predecessor, Disjunctive
+}
+
+impl Kind {
+    /// Offset of the head column after the last node
+    fn list(self) -> usize {
+        match self {
+            Kind::Successor => 0,
+            Kind::Predecessor => 1,
+            Kind::Disjunctive => 2,
+            Kind::Unrelated => unreachable!("Unrelated nodes are not listed")
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct MatrixGraph<T: NodeId + Clone> {
     nodes: Vec<T>,
-    matrix: Vec<Vec<i32>>,
-    x_dim: usize,
+    relations: Vec<Kind>,
+    next: Vec<usize>,
+    previous: Vec<usize>,
+    /// Cell (i, j) is set when successor j of i was a disjunction
+    fixed: Vec<bool>,
+    hash: u64,
+    /// Topological order of the precedences, to keep cycle checks local
+    order: TopologicalOrder
 }
 
-impl<T: NodeId + NodeWeight + Clone> Graph<T> for MatrixGraph<T> {
+impl<T: NodeId + GraphNode + Clone> Graph for MatrixGraph<T> {
+    type Node = T;
+
+    fn create(nodes: Vec<T>, edges: Vec<Vec<Relation>>) -> Self {
+        let n = nodes.len();
+        let width = n + 3;
+
+        let mut graph = MatrixGraph {
+            nodes,
+            relations: vec!(Kind::Unrelated; n * n),
+            next: vec!(0; n * width),
+            previous: vec!(0; n * width),
+            fixed: vec!(false; n * n),
+            hash: 0,
+            order: TopologicalOrder::default()
+        };
+
+        for row in 0..n {
+            for head in n..width {
+                graph.next[row * width + head] = head;
+                graph.previous[row * width + head] = head;
+            }
+        }
+
+        for (row, relations) in edges.iter().enumerate() {
+            for relation in relations {
+                match relation {
+                    Relation::Successor(e) => {
+                        graph.insert(row, *e, Kind::Successor);
+                        graph.hash ^= arc_key(row, *e);
+                    },
+                    Relation::Predecessor(e) => graph.insert(row, *e, Kind::Predecessor),
+                    Relation::Disjunctive(e) => graph.insert(row, *e, Kind::Disjunctive),
+                }
+            }
+        }
+
+        graph.order = TopologicalOrder::new(&graph);
+        graph
+    }
+
     fn nodes(&self) -> &[T] {
-		&self.nodes
-	}
-
-	fn source(&self) -> &T {
-		&self.nodes().first().unwrap()
-	}
-
-	fn sink(&self) -> &T {
-		&self.nodes().last().unwrap()
-	}
-
-    fn successors<'a>(&'a self, id: &impl NodeId) -> Vec<&T> {
-        MatrixIterator::<'a> {
-            matrix: &self.matrix,
-            id: id.id(),
-            nodes: self.nodes.len() as isize,
-            column: self.x_dim - 3
-        }.map(|x| &self.nodes[x]).collect()
-	}
-
-    fn predecessors<'a>(&'a self, id: &impl NodeId) -> Vec<&T> {
-		MatrixIterator::<'a> {
-            matrix: &self.matrix,
-            id: id.id(),
-            nodes: 0,
-            column: self.x_dim - 2
-        }.map(|x| &self.nodes[x]).collect()
-	}
-    fn disjunctions<'a>(&'a self, id: &impl NodeId) -> Vec<&T> {
-		MatrixIterator::<'a> {
-            matrix: &self.matrix,
-            id: id.id(),
-            nodes: -(self.nodes.len() as isize),
-            column: self.x_dim - 1
-        }.map(|x| &self.nodes[x]).collect()
-	}
-
-    fn fix_disjunction(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> disjunctgraph::Result<Self> {
-        let mut cloned = self.clone();
+        &self.nodes
+    }
+
+    fn nodes_mut(&mut self) -> &mut [T] {
+        &mut self.nodes
+    }
+
+    fn source(&self) -> &T {
+        self.nodes().first().unwrap()
+    }
+
+    fn sink(&self) -> &T {
+        self.nodes().last().unwrap()
+    }
+
+    fn successors(&self, id: &impl NodeId) -> NodeIterator<'_, Self> {
+        NodeIterator::boxed(self.list(id.id(), Kind::Successor).map(move |x| &self.nodes[x]))
+    }
+
+    fn predecessors(&self, id: &impl NodeId) -> NodeIterator<'_, Self> {
+        NodeIterator::boxed(self.list(id.id(), Kind::Predecessor).map(move |x| &self.nodes[x]))
+    }
+
+    fn disjunctions(&self, id: &impl NodeId) -> NodeIterator<'_, Self> {
+        NodeIterator::boxed(self.list(id.id(), Kind::Disjunctive).map(move |x| &self.nodes[x]))
+    }
+
+    fn fix_disjunction(&mut self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<(), GraphError> {
+        if !self.has_disjunction(node_1, node_2) {
+            return Err(GraphError::InvalidEdge);
+        }
+
         let node_1 = node_1.id();
         let node_2 = node_2.id();
-        
-        // Remove from disjunctions
-        // Remove from (node_1, node_2)
-        // Remove from (node_2, node_1)
-        // Add node_2 as successor of node_1
-        // Add node_1 as predecessor of node_2
-		unimplemented!()
-	}
-
-    fn flip_edge(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> disjunctgraph::Result<Self> {
-        // Remove node_1 from predecessors of node_2
-        // Remove node_2 from predecessors of node_1
-        // Add node_1 as successor of node_2
-        // Add node_2 as predecessor of node_1
-		unimplemented!()
-	}
-
-    fn into_directed(&self) -> disjunctgraph::Result<Self> {
-        // For every disjunction, flip edge
-		unimplemented!()
-	}
+
+        // Node_2 already reaches node_1
+        if let Some(cycle) = self.precedence_path(&node_2, &node_1) {
+            return Err(GraphError::Cyclic(cycle));
+        }
+
+        // Node_1 -> Node_2
+        self.relate(node_1, node_2, Kind::Successor);
+        self.relate(node_2, node_1, Kind::Predecessor);
+        self.fixed[node_1 * self.nodes.len() + node_2] = true;
+        self.hash ^= arc_key(node_1, node_2);
+
+        let affected = self.order.affected(self, node_1, node_2);
+        self.order.reorder(affected);
+
+        Ok(())
+    }
+
+    fn unfix_disjunction(&mut self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<(), GraphError> {
+        if !self.has_fixed_disjunction(node_1, node_2) {
+            return Err(GraphError::InvalidEdge);
+        }
+
+        let node_1 = node_1.id();
+        let node_2 = node_2.id();
+
+        self.fixed[node_1 * self.nodes.len() + node_2] = false;
+        self.relate(node_1, node_2, Kind::Disjunctive);
+        self.relate(node_2, node_1, Kind::Disjunctive);
+        self.hash ^= arc_key(node_1, node_2);
+
+        Ok(())
+    }
+
+    fn flip_edge(mut self, node_1: &impl NodeId, node_2: &impl NodeId) -> Result<Self, GraphError> {
+        if !self.has_precedence(node_1, node_2) {
+            return Err(GraphError::InvalidEdge);
+        }
+
+        let node_1 = node_1.id();
+        let node_2 = node_2.id();
+
+        // node_1 -> node_2 becomes node_2 -> node_1
+        self.relate(node_1, node_2, Kind::Predecessor);
+
+        // Node_1 reaches node_2 in another way
+        if let Some(cycle) = self.precedence_path(&node_1, &node_2) {
+            return Err(GraphError::Cyclic(cycle));
+        }
+
+        self.relate(node_2, node_1, Kind::Successor);
+        let n = self.nodes.len();
+        if self.fixed[node_1 * n + node_2] {
+            self.fixed[node_1 * n + node_2] = false;
+            self.fixed[node_2 * n + node_1] = true;
+        }
+        self.hash ^= arc_key(node_1, node_2) ^ arc_key(node_2, node_1);
+
+        let affected = self.order.affected(&self, node_2, node_1);
+        self.order.reorder(affected);
+
+        Ok(self)
+    }
+
+    fn into_directed(&self) -> Result<Self, GraphError> {
+        let mut cloned = self.clone();
+        // Every disjunction goes from the lower to the higher id
+        for node_1 in 0..self.nodes.len() {
+            for node_2 in self.list(node_1, Kind::Disjunctive).filter(|node_2| *node_2 > node_1) {
+                cloned.fix_disjunction(&node_1, &node_2)?;
+            }
+        }
+
+        match cloned.find_cycle() {
+            Some(cycle) => Err(disjunctgraph::GraphError::Cyclic(cycle)),
+            None => Ok(cloned)
+        }
+    }
+
+    /// Graph contains relation: node_1 -> node_2
+    fn has_precedence(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool {
+        self.relation(node_1.id(), node_2.id()) == Kind::Successor
+    }
+
+    fn has_disjunction(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool {
+        self.relation(node_1.id(), node_2.id()) == Kind::Disjunctive
+    }
+
+    fn has_fixed_disjunction(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> bool {
+        self.fixed[node_1.id() * self.nodes.len() + node_2.id()]
+    }
+
+    fn node_has_disjunction(&self, node: &impl NodeId) -> bool {
+        let head = self.head(Kind::Disjunctive);
+        self.next[self.cell(node.id(), head)] != head
+    }
+
+    fn precedence_path(&self, node_1: &impl NodeId, node_2: &impl NodeId) -> Option<Vec<usize>> {
+        self.order.path(self, node_1.id(), node_2.id())
+    }
+
+    fn selection_hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl<T: NodeId + Clone> MatrixGraph<T> {
+    fn width(&self) -> usize {
+        self.nodes.len() + 3
+    }
+
+    fn cell(&self, row: usize, column: usize) -> usize {
+        row * self.width() + column
+    }
+
+    fn head(&self, kind: Kind) -> usize {
+        self.nodes.len() + kind.list()
+    }
+
+    fn relation(&self, node_1: usize, node_2: usize) -> Kind {
+        self.relations[node_1 * self.nodes.len() + node_2]
+    }
+
+    /// Members of one list of a row
+    fn list(&self, row: usize, kind: Kind) -> MatrixIterator<'_> {
+        let head = self.head(kind);
+        MatrixIterator {
+            row: &self.next[self.cell(row, 0)..self.cell(row + 1, 0)],
+            column: head,
+            head
+        }
+    }
+
+    /// Put column in front of the list of the kind
+    fn insert(&mut self, row: usize, column: usize, kind: Kind) {
+        let head = self.head(kind);
+        let first = self.next[self.cell(row, head)];
+
+        let (cell, head_cell, first_cell) = (self.cell(row, column), self.cell(row, head), self.cell(row, first));
+        self.next[cell] = first;
+        self.previous[cell] = head;
+        self.previous[first_cell] = column;
+        self.next[head_cell] = column;
+        self.relations[row * self.nodes.len() + column] = kind;
+    }
+
+    fn remove(&mut self, row: usize, column: usize) {
+        let cell = self.cell(row, column);
+        let (previous, next) = (self.previous[cell], self.next[cell]);
+
+        let (previous_cell, next_cell) = (self.cell(row, previous), self.cell(row, next));
+        self.next[previous_cell] = next;
+        self.previous[next_cell] = previous;
+        self.relations[row * self.nodes.len() + column] = Kind::Unrelated;
+    }
+
+    /// Move column to the list of the kind
+    fn relate(&mut self, row: usize, column: usize, kind: Kind) {
+        if self.relation(row, column) != Kind::Unrelated {
+            self.remove(row, column);
+        }
+        self.insert(row, column, kind);
+    }
 }
 
 struct MatrixIterator<'a> {
-    matrix: &'a Vec<Vec<i32>>,
-    id: usize,
-    nodes: isize,
-    column: usize
+    row: &'a [usize],
+    column: usize,
+    head: usize
 }
 
 impl<'a> Iterator for MatrixIterator<'a> {
     type Item = usize;
 
     fn next(&mut self) -> Option<usize> {
-        let old_column = self.column as isize;
-        let next_column = self.matrix[self.id][self.column] as isize - self.nodes;
-        
-        if old_column == next_column {
+        self.column = self.row[self.column];
+
+        if self.column == self.head {
             None
         } else {
-            self.column = next_column as usize;
             Some(self.column)
         }
     }
-}*/
\ No newline at end of file
+}
+
+impl<T: NodeId + Clone> std::ops::Index<usize> for MatrixGraph<T> {
+    type Output = T;
+
+    fn index(&self, node: usize) -> &Self::Output {
+        &self.nodes[node]
+    }
+}
+
+impl<T: NodeId + Clone> std::ops::IndexMut<usize> for MatrixGraph<T> {
+    fn index_mut(&mut self, node: usize) -> &mut T {
+        &mut self.nodes[node]
+    }
+}
+
+impl<T: ConstrainedNode + Clone> std::fmt::Debug for MatrixGraph<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "properties:")?;
+        for node in self.nodes() {
+            writeln!(f, "{}: [head: {}, tail: {}]", node.id(), node.head(), node.tail())?;
+        }
+
+        for (name, kind) in &[("successors", Kind::Successor), ("predecessor", Kind::Predecessor), ("disjunction", Kind::Disjunctive)] {
+            writeln!(f, "{}:", name)?;
+            for node in self.nodes() {
+                writeln!(f, "{} -> {:?}", node.id(), self.list(node.id(), *kind).collect::<Vec<_>>())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LinkedGraph;
+
+    #[derive(Clone, Debug)]
+    struct Node(usize, u32);
+
+    impl NodeId for Node {
+        fn id(&self) -> usize { self.0 }
+    }
+
+    impl GraphNode for Node {
+        fn create(id: usize, weight: u32, _: Option<u32>, _: Option<usize>) -> Self { Node(id, weight) }
+        fn weight(&self) -> u32 { self.1 }
+        fn job_id(&self) -> Option<usize> { None }
+        fn machine_id(&self) -> Option<u32> { None }
+    }
+
+    /// Source -> 1 -> 3 -> sink, source -> 2 -> sink and 1 - 2 - 3 on one machine
+    fn create<G: Graph<Node = Node>>() -> G {
+        use Relation::*;
+        let nodes = vec!(Node(0, 0), Node(1, 2), Node(2, 3), Node(3, 4), Node(4, 0));
+        let edges = vec!(
+            vec!(Successor(1), Successor(2)),
+            vec!(Predecessor(0), Successor(3), Disjunctive(2)),
+            vec!(Predecessor(0), Successor(4), Disjunctive(1), Disjunctive(3)),
+            vec!(Predecessor(1), Successor(4), Disjunctive(2)),
+            vec!(Predecessor(2), Predecessor(3)));
+        G::create(nodes, edges)
+    }
+
+    fn relations<G: Graph<Node = Node>>(graph: &G) -> Vec<(Vec<usize>, Vec<usize>, Vec<usize>)> {
+        let sorted = |iter: NodeIterator<G>| { let mut ids = iter.map(|n| n.id()).collect::<Vec<_>>(); ids.sort(); ids };
+        graph.nodes().iter()
+            .map(|n| (sorted(graph.successors(n)), sorted(graph.predecessors(n)), sorted(graph.disjunctions(n))))
+            .collect()
+    }
+
+    #[test]
+    fn test_matches_linked_graph() {
+        let mut matrix: MatrixGraph<Node> = create();
+        let mut linked: LinkedGraph<Node> = create();
+        assert_eq!(relations(&linked), relations(&matrix));
+
+        for (node_1, node_2) in &[(2, 1), (2, 3)] {
+            matrix.fix_disjunction(node_1, node_2).unwrap();
+            linked.fix_disjunction(node_1, node_2).unwrap();
+            assert_eq!(relations(&linked), relations(&matrix));
+            assert_eq!(linked.selection_hash(), matrix.selection_hash());
+        }
+        assert!(!matrix.has_disjunctions());
+        assert!(matrix.fix_disjunction(&1, &2).is_err());
+        assert_eq!(Some(9), matrix.critical_length().ok());
+
+        let matrix = matrix.flip_edge(&2, &1).unwrap();
+        let linked = linked.flip_edge(&2, &1).unwrap();
+        assert_eq!(relations(&linked), relations(&matrix));
+        assert_eq!(linked.selection_hash(), matrix.selection_hash());
+        assert!(matrix.has_precedence(&1, &2));
+    }
+}
@@ -0,0 +1,123 @@
+//! Topological order of the precedences, kept up to date while arcs are added.
+//! Based on the dynamic topological order of D. J. Pearce and P. H. J. Kelly.
+//!
+//! An arc x -> y can only close a cycle when y is ordered before x, and then the cycle only contains
+//! nodes ordered between y and x. So only those nodes are searched, and only those are moved to restore the order.
+//! Removing an arc keeps the order valid.
+use hashbrown::{ HashMap, HashSet };
+
+use crate::{ Graph, NodeId };
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TopologicalOrder {
+    /// Position of every node in the order
+    position: Vec<usize>,
+}
+
+impl TopologicalOrder {
+    /// Order the precedences of a graph from scratch.
+    /// Nodes on a cycle are put last, the order is only valid for acyclic precedences.
+    pub fn new<G: Graph>(graph: &G) -> Self {
+        let n = graph.nodes().len();
+        let mut in_degree = (0..n).map(|node| graph.predecessors(&node).count()).collect::<Vec<_>>();
+        let mut ready = (0..n).filter(|node| in_degree[*node] == 0).collect::<Vec<_>>();
+        let mut position = vec!(usize::MAX; n);
+        let mut next = 0;
+
+        while let Some(node) = ready.pop() {
+            position[node] = next;
+            next += 1;
+            for successor in graph.successors(&node) {
+                in_degree[successor.id()] -= 1;
+                if in_degree[successor.id()] == 0 {
+                    ready.push(successor.id());
+                }
+            }
+        }
+
+        for node in position.iter_mut().filter(|p| **p == usize::MAX) {
+            *node = next;
+            next += 1;
+        }
+
+        TopologicalOrder { position }
+    }
+
+    /// Node ids of a path from -> ... -> to over the precedences, None if to is not reachable.
+    pub fn path<G: Graph>(&self, graph: &G, from: usize, to: usize) -> Option<Vec<usize>> {
+        let bound = self.position[to];
+        if self.position[from] > bound {
+            return None;
+        }
+
+        let mut parent = HashMap::new();
+        parent.insert(from, from);
+        let mut stack = vec!(from);
+
+        while let Some(node) = stack.pop() {
+            if node == to {
+                let mut path = vec!(to);
+                while *path.last().unwrap() != from {
+                    path.push(parent[path.last().unwrap()]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for successor in graph.successors(&node).map(|s| s.id()) {
+                if self.position[successor] <= bound && !parent.contains_key(&successor) {
+                    parent.insert(successor, node);
+                    stack.push(successor);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Nodes that have to move after the arc from -> to was added, in their new relative order.
+    /// Empty when from was already ordered before to. The arc may not close a cycle.
+    pub fn affected<G: Graph>(&self, graph: &G, from: usize, to: usize) -> Vec<usize> {
+        let (lower, upper) = (self.position[to], self.position[from]);
+        if upper < lower {
+            return Vec::new();
+        }
+
+        // Everything that reaches from has to stay before everything that to reaches.
+        let mut backward = self.reach(from, |p| p > lower, |node| graph.predecessors(&node).map(|n| n.id()));
+        let mut forward = self.reach(to, |p| p < upper, |node| graph.successors(&node).map(|n| n.id()));
+        backward.sort_by_key(|node| self.position[*node]);
+        forward.sort_by_key(|node| self.position[*node]);
+
+        backward.extend(forward);
+        backward
+    }
+
+    /// Give the nodes the positions they occupy together, in the given order.
+    pub fn reorder(&mut self, nodes: Vec<usize>) {
+        let mut positions = nodes.iter().map(|node| self.position[*node]).collect::<Vec<_>>();
+        positions.sort();
+        for (node, position) in nodes.into_iter().zip(positions) {
+            self.position[node] = position;
+        }
+    }
+
+    /// Nodes reachable from start over related nodes, only visiting nodes with a position within the range.
+    fn reach<I: Iterator<Item = usize>>(&self, start: usize, within: impl Fn(usize) -> bool, mut related: impl FnMut(usize) -> I) -> Vec<usize> {
+        let mut visited = HashSet::new();
+        visited.insert(start);
+        let mut stack = vec!(start);
+        let mut reached = Vec::new();
+
+        while let Some(node) = stack.pop() {
+            reached.push(node);
+            for next in related(node) {
+                if within(self.position[next]) && visited.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+
+        reached
+    }
+}
@@ -0,0 +1,80 @@
+//! Zobrist hashing of partial selections and a bounded transposition table.
+//!
+//! Every arc node_1 -> node_2 has a fixed pseudo random key, the hash of a graph
+//! is the xor of the keys of all its precedences. Fixing or flipping an arc only
+//! changes the hash by the keys of that arc, so it can be maintained incrementally.
+use crate::{ ConstrainedNode, Graph, NodeId };
+
+/// Key of the arc node_1 -> node_2
+pub fn arc_key(node_1: usize, node_2: usize) -> u64 {
+    splitmix64(((node_1 as u64) << 32) | node_2 as u64)
+}
+
+/// Key of a node having the given head and tail
+pub fn window_key(node: usize, head: u32, tail: u32) -> u64 {
+    splitmix64(splitmix64(!(node as u64)) ^ ((head as u64) << 32 | tail as u64))
+}
+
+/// Hash of the selection combined with the head and tail of every node.
+/// Two graphs with the same selection but different windows are different search states.
+pub fn state_hash<G: Graph>(graph: &G) -> u64 where G::Node: ConstrainedNode {
+    graph.nodes().iter()
+        .fold(graph.selection_hash(), |hash, node| hash ^ window_key(node.id(), node.head(), node.tail()))
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Fixed size table of visited search states.
+/// A state that was visited with an upper bound at least as large as the current one
+/// has already been (or will be) searched for all solutions within that bound.
+pub struct TranspositionTable {
+    entries: Vec<Option<(u64, u32)>>,
+    hits: usize,
+}
+
+impl TranspositionTable {
+    pub fn new(size: usize) -> Self {
+        TranspositionTable { entries: vec!(None; size), hits: 0 }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.entries.is_empty()
+    }
+
+    /// Number of states that were skipped
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Returns true when the state was already visited with an upper bound of at least `upper_bound`,
+    /// otherwise the state is stored, replacing whatever was in its slot.
+    pub fn visit(&mut self, hash: u64, upper_bound: u32) -> bool {
+        if !self.enabled() {
+            return false;
+        }
+
+        let slot = (hash % self.entries.len() as u64) as usize;
+        match self.entries[slot] {
+            Some((stored, bound)) if stored == hash && bound >= upper_bound => {
+                self.hits += 1;
+                true
+            },
+            _ => {
+                self.entries[slot] = Some((hash, upper_bound));
+                false
+            }
+        }
+    }
+
+    /// Forget all states, needed when a search is abandoned before the stored states were searched.
+    pub fn clear(&mut self) {
+        for entry in self.entries.iter_mut() {
+            *entry = None;
+        }
+    }
+}
@@ -1,18 +1,192 @@
-use crate::{ ConstrainedNode, Graph, NodeId, GraphNode };
+use crate::{ ConstrainedNode, Graph, GraphNode, GraphError, NodeId };
 use itertools::Itertools;
 
-fn find_orders<T: Graph>(resources: &[u32], graph: T) -> Result<Vec<(usize, usize)>, ()> where T::Node: ConstrainedNode {
+/// Orders and window adjustments that are implied on the resources.
+#[derive(Debug, Default)]
+pub struct Deductions {
+    /// Precedences node_1 -> node_2 that have to hold
+    pub orders: Vec<(usize, usize)>,
+    /// Lower bounds on the head of a node
+    pub heads: Vec<(usize, u32)>,
+    /// Lower bounds on the tail of a node
+    pub tails: Vec<(usize, u32)>,
+}
 
-    let orders = Vec::new();
+impl Deductions {
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty() && self.heads.is_empty() && self.tails.is_empty()
+    }
+
+    pub fn extend(&mut self, other: Deductions) {
+        self.orders.extend(other.orders);
+        self.heads.extend(other.heads);
+        self.tails.extend(other.tails);
+    }
+}
+
+/// Search the operations on every resource for orders implied by the upper bound.
+/// The graph is not changed, the deductions are returned.
+///
+/// Two rules are used:
+/// 1. Detectable precedences (P. Vilím): if ect(i) > lst(j) then j has to precede i.
+///    All detectable predecessors of i precede it, so head(i) >= ECT of that set. Symmetric for tails.
+/// 2. Immediate selection on task intervals (J. Carlier and E. Pinson): for a task interval J and c not in J,
+///    if c is not last then all of J and c are processed between min head and the smallest tail of J.
+///    If that does not fit in the upper bound, c is processed after all of J. Symmetric for c first.
+pub fn find_orders<T: Graph>(resources: &[u32], graph: &T, upper_bound: u32) -> Result<Deductions, GraphError> where T::Node: ConstrainedNode {
+
+    let mut deductions = Deductions::default();
 
     for resource in resources {
         let resource = graph.nodes().iter().filter(|n| n.machine_id() == Some(*resource)).collect_vec();
-        let heads = resource.iter().sorted_by_key(|n| n.head());
-        let tails = resource.iter().sorted_by_key(|n| n.tail());
-        
-        
 
+        detectable_precedences(&resource, upper_bound, &mut deductions)?;
+        immediate_selection(&resource, upper_bound, &mut deductions)?;
+    }
+
+    deductions.orders = deductions.orders.into_iter().unique().collect();
+    Ok(deductions)
+}
+
+/// Raise the heads and tails of the deductions and push every change along the precedences.
+/// The orders are not applied. Returns whether a head or tail changed.
+pub fn apply_windows<T: Graph>(deductions: &Deductions, graph: &mut T, upper_bound: u32) -> Result<bool, GraphError> where T::Node: ConstrainedNode {
+    let mut changed = false;
+    for (node, head) in &deductions.heads {
+        changed |= raise_head(graph, *node, *head, upper_bound)?;
+    }
+    for (node, tail) in &deductions.tails {
+        changed |= raise_tail(graph, *node, *tail, upper_bound)?;
+    }
+    Ok(changed)
+}
+
+fn raise_head<T: Graph>(graph: &mut T, node: usize, head: u32, upper_bound: u32) -> Result<bool, GraphError> where T::Node: ConstrainedNode {
+    let mut stack = vec!((node, head));
+    let mut changed = false;
+    while let Some((id, head)) = stack.pop() {
+        let node = &mut graph[id];
+        if head > node.head() {
+            if head + node.weight() + node.tail() > upper_bound {
+                return Err(GraphError::Infeasible);
+            }
+            node.set_head(head);
+            let next_head = head + node.weight();
+            stack.extend(graph.successors(&id).map(|s| (s.id(), next_head)));
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+fn raise_tail<T: Graph>(graph: &mut T, node: usize, tail: u32, upper_bound: u32) -> Result<bool, GraphError> where T::Node: ConstrainedNode {
+    let mut stack = vec!((node, tail));
+    let mut changed = false;
+    while let Some((id, tail)) = stack.pop() {
+        let node = &mut graph[id];
+        if tail > node.tail() {
+            if node.head() + node.weight() + tail > upper_bound {
+                return Err(GraphError::Infeasible);
+            }
+            node.set_tail(tail);
+            let next_tail = tail + node.weight();
+            stack.extend(graph.predecessors(&id).map(|p| (p.id(), next_tail)));
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+fn detectable_precedences<N: ConstrainedNode>(resource: &[&N], upper_bound: u32, deductions: &mut Deductions) -> Result<(), GraphError> {
+    let ect = |n: &N| n.head() + n.weight();
+    let lst = |n: &N| n.lst(upper_bound);
+
+    for i in resource {
+        // j can not be after i, as i ends after j has to start.
+        let predecessors = resource.iter()
+            .filter(|j| j.id() != i.id() && ect(i) > lst(j))
+            .collect_vec();
+
+        if predecessors.iter().any(|j| ect(j) > lst(i)) {
+            return Err(GraphError::Infeasible);
+        }
+
+        deductions.orders.extend(predecessors.iter().map(|j| (j.id(), i.id())));
+
+        let head = predecessors.iter()
+            .sorted_by_key(|j| j.head())
+            .fold(0, |ect, j| std::cmp::max(ect, j.head()) + j.weight());
+        if head > i.head() {
+            deductions.heads.push((i.id(), head));
+        }
+
+        // Symmetric: j can not be before i, as j ends after i has to start
+        let successors = resource.iter()
+            .filter(|j| j.id() != i.id() && ect(j) > lst(i))
+            .collect_vec();
+
+        deductions.orders.extend(successors.iter().map(|j| (i.id(), j.id())));
+
+        let tail = successors.iter()
+            .sorted_by_key(|j| j.tail())
+            .fold(0, |ect, j| std::cmp::max(ect, j.tail()) + j.weight());
+        if tail > i.tail() {
+            deductions.tails.push((i.id(), tail));
+        }
+    }
+
+    Ok(())
+}
+
+fn immediate_selection<N: ConstrainedNode>(resource: &[&N], upper_bound: u32, deductions: &mut Deductions) -> Result<(), GraphError> {
+
+    for (a, b) in resource.iter().cartesian_product(resource.iter()) {
+        // Task interval J: all operations with a head of at least head(a) and a tail of at least tail(b)
+        let (interval, others): (Vec<&N>, Vec<&N>) = resource.iter()
+            .partition(|k| k.head() >= a.head() && k.tail() >= b.tail());
+
+        if interval.is_empty() {
+            continue;
+        }
+
+        let head = interval.iter().map(|k| k.head()).min().unwrap();
+        let tail = interval.iter().map(|k| k.tail()).min().unwrap();
+        let processing: u32 = interval.iter().map(|k| k.weight()).sum();
+
+        if head + processing + tail > upper_bound {
+            return Err(GraphError::Infeasible);
+        }
+
+        for c in others {
+            // If c is not last, one of J is last
+            let last = std::cmp::min(head, c.head()) + processing + c.weight() + tail > upper_bound;
+            // If c is not first, one of J is first
+            let first = head + processing + c.weight() + std::cmp::min(tail, c.tail()) > upper_bound;
+
+            match (first, last) {
+                (true, true) => return Err(GraphError::Infeasible),
+                (false, true) => {
+                    deductions.orders.extend(interval.iter().map(|k| (k.id(), c.id())));
+                    let ect = interval.iter()
+                        .sorted_by_key(|k| k.head())
+                        .fold(0, |ect, k| std::cmp::max(ect, k.head()) + k.weight());
+                    if ect > c.head() {
+                        deductions.heads.push((c.id(), ect));
+                    }
+                },
+                (true, false) => {
+                    deductions.orders.extend(interval.iter().map(|k| (c.id(), k.id())));
+                    let ect = interval.iter()
+                        .sorted_by_key(|k| k.tail())
+                        .fold(0, |ect, k| std::cmp::max(ect, k.tail()) + k.weight());
+                    if ect > c.tail() {
+                        deductions.tails.push((c.id(), ect));
+                    }
+                },
+                (false, false) => ()
+            }
+        }
     }
 
-    Ok(orders)
-}
\ No newline at end of file
+    Ok(())
+}
@@ -18,6 +18,8 @@ use widget_graph::*;
 use widget_constraints::*;
 use widget_edge_selection::*;
 use widget_edge_selection::EdgeMsg::Fix as EdgeFix;
+use widget_edge_selection::EdgeMsg::Unfix as EdgeUnfix;
+use widget_edge_selection::EdgeMsg::Swap as EdgeSwap;
 
 use gtk::prelude::*;
 use gtk::Orientation::{ Vertical, Horizontal };
@@ -36,9 +38,11 @@ pub enum Msg {
     Decrement,
     Increment,
     Fix(usize, usize),
+    Unfix(usize, usize),
+    Swap(usize, usize),
     Quit,
 }
-use Msg::Fix;
+use Msg::{ Fix, Unfix, Swap };
 
 pub struct Model {
     counter: u32,
@@ -125,6 +129,30 @@ impl Widget for Win {
                     println!("Leads to infeasible solution");
                 }
             },
+            Msg::Unfix(a, b) => {
+                let mut graph = self.model.graph.clone();
+                if graph.unfix_disjunction(&a, &b).is_err() {
+                    return;
+                }
+                // Rejected when the bounds of the unfixed graph exceed the upper bound
+                if let Ok(constraints) = ProblemConstraints::new(&graph, UPPER) {
+                    self.model.graph = graph;
+                    self.graph.emit(GraphMsg::SetProblem((self.model.problem.clone(), self.model.graph.clone())));
+                    self.constraints.emit(ConstraintsMsg::SetProblem((self.model.problem.clone(), constraints)));
+                    self.edge_selection.emit(EdgeMsg::SetProblem(self.model.graph.clone()));
+                }
+            },
+            Msg::Swap(a, b) => {
+                // Rejected when swapping leads to a cycle or exceeds the upper bound
+                let swapped = self.model.graph.clone().flip_edge(&a, &b).ok()
+                    .and_then(|graph| ProblemConstraints::new(&graph, UPPER).ok().map(|constraints| (graph, constraints)));
+                if let Some((graph, constraints)) = swapped {
+                    self.model.graph = graph;
+                    self.graph.emit(GraphMsg::SetProblem((self.model.problem.clone(), self.model.graph.clone())));
+                    self.constraints.emit(ConstraintsMsg::SetProblem((self.model.problem.clone(), constraints)));
+                    self.edge_selection.emit(EdgeMsg::SetProblem(self.model.graph.clone()));
+                }
+            },
             Msg::Quit => gtk::main_quit(),
         }
     }
@@ -166,6 +194,8 @@ impl Widget for Win {
                         #[name="edge_selection"]
                         EdgeSelection<LinkedGraph<ProblemNode>>(self.model.graph.clone()) {
                             EdgeFix(a, b) => Fix(a, b),
+                            EdgeUnfix(a, b) => Unfix(a, b),
+                            EdgeSwap(a, b) => Swap(a, b),
                         },
                     },
         
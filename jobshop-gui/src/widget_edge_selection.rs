@@ -89,6 +89,33 @@ impl<I: Graph + 'static> Widget for EdgeSelection<I> {
             container.add(&mini_box);
         }
 
+        let fixed = graph.nodes()
+            .iter()
+            .map(|x| x.id())
+            .map(|id| graph.successors(&id).into_iter().map(move |x| (id, x.id())) )
+            .flatten()
+            .filter(|(a, b)| graph.has_fixed_disjunction(a, b) );
+
+        for (a, b) in fixed {
+
+            let mini_box = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+            let label = format!("Unfix {} -> {}", a, b);
+            let button = gtk::Button::new();
+            button.set_label(&label);
+            mini_box.add(&button);
+
+            connect!(relm, button, connect_clicked(_), Some(EdgeMsg::Unfix(a, b)));
+
+            let label = format!("Swap to {} -> {}", b, a);
+            let button: gtk::Button = gtk::Button::new();
+            button.set_label(&label);
+            mini_box.add(&button);
+
+            connect!(relm, button, connect_clicked(_), Some(EdgeMsg::Swap(a, b)));
+
+            container.add(&mini_box);
+        }
+
         scrollbar.add(&container);
         scroll_container.add(&scrollbar);
         scrollbar.show_all();
@@ -119,7 +119,8 @@ fn load(index: usize, state: State<GraphState>) -> rocket::http::Status {
 
 #[derive(Serialize)]
 struct SynchronizedState {
-
+    problem: Problem,
+    graph: LinkedGraph,
 }
 
 #[derive(Serialize)]
@@ -129,12 +130,17 @@ struct WrongState;
 #[get("/sync")]
 fn synchronize(state: State<GraphState>) -> Result<Json<SynchronizedState>, rocket::http::Status> {
     
-    if let None = *state.inner().lock().unwrap().borrow() {
-        return Result::Err(rocket::http::Status::new(400, "No state is yet defined"));
-    }
-
-    let sync = SynchronizedState {};
-
+    let guard = state.inner().lock().unwrap();
+    let program_state = guard.borrow();
+    let program_state = match &*program_state {
+        Some(program_state) => program_state,
+        None => return Result::Err(rocket::http::Status::new(400, "No state is yet defined"))
+    };
+
+    let sync = SynchronizedState {
+        problem: program_state.problem.clone(),
+        graph: program_state.graph.clone(),
+    };
 
     Ok(Json(sync))
 }
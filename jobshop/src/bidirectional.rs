@@ -0,0 +1,115 @@
+//! Racing the search on a problem against the search on its reverse.
+//! Heads and tails swap roles in the reverse, some instances are much easier that way around.
+use crate::problem::Problem;
+use disjunctgraph::{ ConstrainedNode, Graph };
+
+use std::sync::{ Arc, mpsc };
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::thread;
+
+/// Solve the problem and its reverse with `solve` on separate threads. Once one search
+/// returns a schedule without disjunctions left the other is told to stop. Both threads are
+/// joined and an oriented schedule is returned, as a solution of `problem`, if either found one.
+pub fn race<I, F>(problem: &Problem, solve: F) -> I
+where I: Graph + Send + 'static, I::Node: ConstrainedNode, F: Fn(&Problem, Arc<AtomicBool>) -> I + Send + Sync + 'static {
+    let stop = Arc::new(AtomicBool::new(false));
+    let solve = Arc::new(solve);
+    let (sender, receiver) = mpsc::channel();
+
+    let handles: Vec<_> = [false, true].iter().map(|reverse| {
+        let reverse = *reverse;
+        let problem = if reverse { problem.reversed() } else { problem.clone() };
+        let (solve, stop, sender) = (solve.clone(), stop.clone(), sender.clone());
+        thread::spawn(move || {
+            let solution = solve(&problem, stop);
+            let _ = sender.send((reverse, solution));
+        })
+    }).collect();
+    drop(sender);
+
+    // A search that ends with disjunctions left failed, the other one may still succeed
+    let mut solutions = Vec::with_capacity(2);
+    for (reverse, solution) in receiver.iter() {
+        let oriented = !solution.has_disjunctions();
+        solutions.push((reverse, solution));
+        if oriented {
+            break;
+        }
+    }
+    stop.store(true, Ordering::Relaxed);
+    for handle in handles {
+        // A panicking search has sent nothing
+        let _ = handle.join();
+    }
+
+    // An oriented schedule, otherwise the forward result since the reverse can not be read backwards
+    let (reverse, solution) = match solutions.iter().position(|(_, s)| !s.has_disjunctions()) {
+        Some(i) => solutions.swap_remove(i),
+        None => solutions.into_iter().find(|(reverse, _)| !reverse).expect("The forward search panicked")
+    };
+
+    if reverse {
+        let mut graph = problem.from_reversed(&solution).expect("Solution of the reverse does not orient the problem");
+        graph.init_weights();
+        graph
+    } else {
+        solution
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpbab::CPBAB;
+    use crate::problem::{ ProblemSolver, small_problem };
+
+    #[test]
+    fn test_reversed() {
+        let problem = small_problem();
+        let reversed = problem.reversed();
+        assert_eq!(vec!(3, 2, 1), reversed.jobs[0].iter().map(|a| reversed.activities[*a].machine_id).collect::<Vec<_>>());
+        assert_eq!(vec!(3), reversed.activities[4].precedences);
+        assert_eq!(problem.activities, reversed.reversed().activities);
+        assert_eq!(4, problem.reversed_activity(3));
+        assert_eq!(7, problem.reversed_activity(5));
+    }
+
+    #[test]
+    fn test_race() {
+        let problem = small_problem();
+        let graph = CPBAB::new().bidirectional().solve(&problem);
+        assert!(!graph.has_disjunctions());
+        assert_eq!(13, graph.critical_length().unwrap());
+
+        // A solution of the reverse read backwards
+        let reversed = CPBAB::new().solve(&problem.reversed());
+        let graph: cpbab::CGraph = problem.from_reversed(&reversed).unwrap();
+        assert!(!graph.has_disjunctions());
+        assert_eq!(13, graph.critical_length().unwrap());
+    }
+
+    #[test]
+    fn test_race_prefers_oriented() {
+        // The forward search gives up at once, the race waits for the reverse
+        let problem = small_problem();
+        let forward = problem.activities.clone();
+        let graph = race(&problem, move |problem, _| if problem.activities == forward {
+            problem.into_graph::<cpbab::CGraph>()
+        } else {
+            CPBAB::new().solve(problem)
+        });
+        assert!(!graph.has_disjunctions());
+        assert_eq!(13, graph.critical_length().unwrap());
+    }
+
+    #[test]
+    fn test_race_ignores_recorder() {
+        use ::cpbab::{ SearchConfig, SearchTree };
+        use std::sync::Mutex;
+        let tree = Arc::new(Mutex::new(SearchTree::new()));
+        let config = SearchConfig { record: Some(tree.clone()), ..Default::default() };
+        let graph = CPBAB::with_config(config).bidirectional().solve(&small_problem());
+        assert_eq!(13, graph.critical_length().unwrap());
+        assert_eq!(0, tree.lock().unwrap().len());
+    }
+}
@@ -1,103 +1,341 @@
-use disjunctgraph::{ Graph, GraphNode, NodeId };
-use hashbrown::HashSet;
-use std::collections::VecDeque;
+use std::marker::PhantomData;
 
-use crate::problem::{ ProblemSolver, Problem, ProblemNode };
+use disjunctgraph::{ ConstrainedNode, Graph, GraphNode, NodeId };
+use itertools::Itertools;
 
-// In the case of a search, it might be nice to only store partial orientations.
-// As described in https://pure.tue.nl/ws/files/2119953/385216.pdf
-pub struct BranchAndBound;
+use crate::problem::{ ProblemSolver, Problem };
 
-type LinkedGraph = disjunctgraph::LinkedGraph<ProblemNode>;
+/// Block branch and bound of P. Brucker, B. Jurisch and B. Sievers,
+/// A branch and bound algorithm for the job-shop scheduling problem (1994).
+///
+/// Every search node is a partial selection. A heuristic completes it into a schedule and a
+/// critical path of that schedule is split in blocks: maximal runs of operations on one machine.
+/// A better schedule has to move an operation of some block before its first or after its last operation.
+pub struct BranchAndBound<G = cpbab::CGraph> {
+    local_search: u32,
+    graph: PhantomData<fn() -> G>,
+}
 
 impl BranchAndBound {
     pub fn new() -> Self {
-        BranchAndBound {}
+        BranchAndBound { local_search: 2000, graph: PhantomData }
+    }
+}
+
+impl<G> BranchAndBound<G> {
+    /// Search with another graph representation.
+    pub fn with_graph<H>(self) -> BranchAndBound<H> {
+        BranchAndBound { local_search: self.local_search, graph: PhantomData }
     }
 }
-impl ProblemSolver for BranchAndBound {
-    type Solution = LinkedGraph;
+
+impl Default for BranchAndBound {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: Graph + Clone> ProblemSolver for BranchAndBound<G> where G::Node: ConstrainedNode {
+    type Solution = G;
 
     fn solve(&self, problem: &Problem) -> Self::Solution {
+        let resources = (1..=problem.machines).collect_vec();
+
         // Calculate a solution using heuristics
-        let mut upper_graph: LinkedGraph = crate::local_search::LocalSearch::new(2000).solve(problem);
-        let mut upper_bound  = upper_graph.critical_length().unwrap();
-
-        let mut stack = VecDeque::with_capacity(problem.activities.len() * 10);
-        
-        stack.push_back(problem.into_graph());
-
-        while !stack.is_empty() {
-            let current_node: LinkedGraph = stack.pop_back().unwrap();
-            let new_upper_bound = calculate_upperbound(&current_node);
-            
-            if new_upper_bound.1 < upper_bound {
-                upper_graph = new_upper_bound.0;
-                upper_bound = new_upper_bound.1;
+        let local = crate::local_search::LocalSearch::new(self.local_search).solve(problem);
+        let mut upper_graph: G = problem.into_graph();
+        for (node_1, node_2) in arcs(&local) {
+            if upper_graph.has_disjunction(&node_1, &node_2) {
+                upper_graph.fix_disjunction(&node_1, &node_2).expect("Could not copy local search solution");
             }
+        }
+        upper_graph.init_weights();
+        let mut upper_bound = upper_graph.critical_length().expect("Local search solution is cyclic");
 
-            let (_, crit_path) = current_node.critical_path().unwrap();
+        let mut stack: Vec<G> = vec!(problem.into_graph());
 
-            let blocks = calculate_blocks(crit_path);
-            let before_sets = calculate_pre_set(&current_node, &blocks).into_iter().map(|x| (true, x));
-            let after_sets = calculate_pre_set(&current_node, &blocks).into_iter().map(|x| (false, x));
-
-            // Handle Before sets then after sets
-            for (before, set) in before_sets.chain(after_sets) {
-                for operation in set {
-                    // Create a successor node
-                    let successor = current_node.clone();
-                    // Fix the disjunctions, let operation be in front of all its disjunctions
-                    let successor = current_node
-                        .disjunctions(operation)
-                        .into_iter()
-                        .fold(successor, |acc, disjunction| 
-                            match before {
-                                true => acc.fix_disjunction(operation, disjunction).unwrap(),
-                                false => acc.fix_disjunction(disjunction, operation).unwrap(),
-                            }                            
-                        );
-
-                    // Calculate lower bound
-                    let lowerbound: u32 = calculate_lowerbound(&successor);
-                    // Lowerbound is under UB, add to stack
-                    if lowerbound < upper_bound {
-                        stack.push_back(successor);
-                    }
-                }
+        while let Some(mut current_node) = stack.pop() {
+            if !immediate_selection(&mut current_node, &resources, upper_bound) {
+                continue;
+            }
+
+            let schedule = complete(&current_node);
+            let length = schedule.critical_length().expect("Completed schedule is cyclic");
+            if length < upper_bound {
+                upper_bound = length;
+                upper_graph = schedule.clone();
             }
+
+            // The heuristic might have found the best schedule of this node
+            if lower_bound(&current_node, &resources) >= upper_bound {
+                continue;
+            }
+
+            let (_, crit_path) = schedule.critical_path().expect("Completed schedule is cyclic");
+            let blocks = calculate_blocks(crit_path);
+
+            // The first child is explored first
+            stack.extend(children(&current_node, &blocks).into_iter().rev());
         }
 
         upper_graph
     }
 }
 
-fn calculate_blocks(crit_path: Vec<&ProblemNode>) -> Vec<Vec<&ProblemNode>> {
-    use itertools::Itertools;
+/// Precedences node_1 -> node_2 of the graph
+fn arcs<G: Graph>(graph: &G) -> Vec<(usize, usize)> {
+    graph.nodes().iter()
+        .flat_map(|node| graph.successors(node).map(move |other| (node.id(), other.id())))
+        .collect()
+}
 
+fn calculate_blocks<N: GraphNode>(crit_path: Vec<&N>) -> Vec<Vec<usize>> {
     crit_path.into_iter()
         .group_by(|x| x.machine_id())
         .into_iter()
-        .map(|group| group.1.collect::<Vec<_>>  ())
+        .map(|group| group.1.map(|x| x.id()).collect::<Vec<_>>())
         .filter(|group| group.len() > 1)
         .collect()
 }
 
-// Calculate E_j^B for j \in {0, ..., k}
-fn calculate_pre_set<'a>(graph: &LinkedGraph, blocks: &Vec<Vec<&'a ProblemNode>>) -> Vec<HashSet<&'a ProblemNode>> {
-    blocks.iter()
-        .map(|group| 
-            group.into_iter()
-                .filter(|node| graph.disjunctions(**node).len() > 0)
-                .cloned()
-                .collect::<HashSet<_>>()
-        ).collect()
+/// Children of a node, for every block j in order:
+/// 1. for every i in E_j^B, i before all other operations of the block,
+/// 2. for every i in E_j^A, the first operation stays first and i goes after all other operations.
+///
+/// Children of block j keep the first and last operations of the blocks before j in place,
+/// which makes the children disjoint.
+fn children<G: Graph + Clone>(graph: &G, blocks: &[Vec<usize>]) -> Vec<G> {
+    let mut children = Vec::new();
+    let mut fixed: Vec<(usize, usize)> = Vec::new();
+
+    for block in blocks {
+        let (first, last) = (block[0], block[block.len() - 1]);
+        let others = |i: usize| block.iter().filter(move |k| **k != i).cloned();
+
+        // E_j^B: operations that can still be moved before the first
+        for i in block.iter().skip(1).cloned() {
+            if others(i).any(|k| graph.has_precedence(&k, &i)) {
+                continue;
+            }
+            let selection = fixed.iter().cloned().chain(others(i).map(|k| (i, k)));
+            if let Some(child) = with_arcs(graph, selection) {
+                children.push(child);
+            }
+        }
+
+        let first_fixed = others(first).map(|k| (first, k)).collect_vec();
+
+        // E_j^A: operations that can still be moved after the last
+        for i in block.iter().skip(1).take(block.len() - 2).cloned() {
+            if others(i).any(|k| graph.has_precedence(&i, &k)) {
+                continue;
+            }
+            let selection = fixed.iter().cloned()
+                .chain(first_fixed.iter().cloned())
+                .chain(others(i).map(|k| (k, i)));
+            if let Some(child) = with_arcs(graph, selection) {
+                children.push(child);
+            }
+        }
+
+        fixed.extend(first_fixed);
+        fixed.extend(others(last).map(|k| (k, last)));
+    }
+
+    children
 }
 
-fn calculate_lowerbound(graph: &LinkedGraph) -> u32 {
-    unimplemented!()
+/// Copy of the graph with the precedences added, None if that contradicts the graph.
+fn with_arcs<G: Graph + Clone>(graph: &G, arcs: impl IntoIterator<Item = (usize, usize)>) -> Option<G> {
+    let mut child = graph.clone();
+    for (node_1, node_2) in arcs {
+        if child.has_disjunction(&node_1, &node_2) {
+            child.fix_disjunction(&node_1, &node_2).ok()?;
+        } else if !child.has_precedence(&node_1, &node_2) {
+            return None;
+        }
+    }
+
+    if child.is_cyclic() {
+        None
+    } else {
+        Some(child)
+    }
 }
 
-fn calculate_upperbound(graph: &LinkedGraph) -> (LinkedGraph, u32) {
-    unimplemented!()
+/// Calculate heads and tails and fix the orders implied by the upper bound until nothing changes.
+/// Returns false when the node can not contain a schedule shorter than the upper bound.
+fn immediate_selection<G: Graph>(graph: &mut G, resources: &[u32], upper_bound: u32) -> bool where G::Node: ConstrainedNode {
+    loop {
+        if graph.is_cyclic() {
+            return false;
+        }
+        graph.init_weights();
+
+        if lower_bound(graph, resources) >= upper_bound {
+            return false;
+        }
+
+        // Only schedules shorter than the upper bound are of interest
+        let deductions = match disjunctgraph::find_orders(resources, graph, upper_bound - 1) {
+            Ok(deductions) => deductions,
+            Err(_) => return false
+        };
+
+        let mut changed = false;
+        for (node_1, node_2) in deductions.orders {
+            if graph.has_disjunction(&node_1, &node_2) {
+                if graph.fix_disjunction(&node_1, &node_2).is_err() {
+                    return false;
+                }
+                changed = true;
+            } else if graph.has_precedence(&node_2, &node_1) {
+                return false;
+            }
+        }
+
+        if !changed {
+            return true;
+        }
+    }
+}
+
+/// The longest path through an operation, and the preemptive one machine bound of every machine.
+fn lower_bound<G: Graph>(graph: &G, resources: &[u32]) -> u32 where G::Node: ConstrainedNode {
+    let paths = graph.nodes().iter()
+        .map(|n| n.head() + n.weight() + n.tail())
+        .max().unwrap_or(0);
+
+    resources.iter()
+        .map(|resource| jackson_preemptive(graph.nodes().iter().filter(|n| n.machine_id() == Some(*resource))))
+        .fold(paths, std::cmp::max)
+}
+
+/// Length of Jackson's preemptive schedule: at any time the released operation with the largest tail is processed.
+fn jackson_preemptive<'a, N: ConstrainedNode + 'a>(operations: impl Iterator<Item = &'a N>) -> u32 {
+    let mut operations = operations.map(|n| (n.head(), n.weight(), n.tail())).collect_vec();
+    let mut length = 0;
+    let mut time = 0;
+
+    while !operations.is_empty() {
+        let released = operations.iter().enumerate()
+            .filter(|(_, (head, _, _))| *head <= time)
+            .max_by_key(|(_, (_, _, tail))| *tail)
+            .map(|(i, _)| i);
+
+        match released {
+            None => time = operations.iter().map(|(head, _, _)| *head).min().unwrap_or(time),
+            Some(i) => {
+                // Run until it completes or the next release
+                let next_release = operations.iter().map(|(head, _, _)| *head).filter(|head| *head > time).min();
+                let run = next_release.map_or(operations[i].1, |release| std::cmp::min(operations[i].1, release - time));
+                time += run;
+                operations[i].1 -= run;
+                if operations[i].1 == 0 {
+                    length = std::cmp::max(length, time + operations[i].2);
+                    operations.swap_remove(i);
+                }
+            }
+        }
+    }
+
+    length
+}
+
+/// Complete the selection with a list schedule: the operation that can start first is scheduled next,
+/// ties broken by the largest tail. Every operation is placed before the unscheduled operations on its machine.
+fn complete<G: Graph + Clone>(graph: &G) -> G where G::Node: ConstrainedNode {
+    let mut schedule = graph.clone();
+    let nodes = graph.nodes().len();
+
+    let mut waiting_for = graph.nodes().iter().map(|n| graph.predecessors(n).count()).collect_vec();
+    let mut release = vec!(0u32; nodes);
+    let mut machine_free = vec!(0u32; graph.nodes().iter().filter_map(|n| n.machine_id()).max().unwrap_or(0) as usize + 1);
+    let mut ready = vec!(graph.source().id());
+
+    while !ready.is_empty() {
+        let start = |id: usize| std::cmp::max(release[id], graph[id].machine_id().map_or(0, |m| machine_free[m as usize]));
+        let (index, id) = ready.iter().cloned().enumerate()
+            .min_by_key(|(_, id)| (start(*id), std::cmp::Reverse(graph[*id].tail())))
+            .expect("Ready is not empty");
+        ready.swap_remove(index);
+
+        let node = &graph[id];
+        let end = start(id) + node.weight();
+        if let Some(machine) = node.machine_id() {
+            machine_free[machine as usize] = end;
+            for other in schedule.disjunctions(&id).map(|n| n.id()).collect_vec() {
+                schedule.fix_disjunction(&id, &other).expect("Disjunction disappeared");
+            }
+        }
+
+        // Arcs added to the schedule start at scheduled operations, only the arcs of the graph have to be waited for
+        for successor in graph.successors(&id) {
+            let successor = successor.id();
+            release[successor] = std::cmp::max(release[successor], end);
+            waiting_for[successor] -= 1;
+            if waiting_for[successor] == 0 {
+                ready.push(successor);
+            }
+        }
+    }
+
+    schedule.init_weights();
+    schedule
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_and_bound() {
+        let problem = Problem::from_reader(r"3
+3
+13
+3 2 3
+3 4
+6 3 2
+1 2 3
+3 2
+2 1 3".as_bytes()).unwrap();
+
+        let graph = BranchAndBound::new().solve(&problem);
+        assert!(!graph.has_disjunctions());
+        assert_eq!(13, graph.critical_length().unwrap());
+    }
+
+    #[test]
+    fn test_matrix_graph() {
+        use disjunctgraph::MatrixGraph;
+        let problem = Problem::from_reader(r"3
+3
+13
+3 2 3
+3 4
+6 3 2
+1 2 3
+3 2
+2 1 3".as_bytes()).unwrap();
+
+        let local = crate::local_search::LocalSearch::new(100).with_graph::<MatrixGraph<crate::problem::ProblemNode>>().solve(&problem);
+        assert!(!local.has_disjunctions());
+
+        let graph = BranchAndBound::new().with_graph::<MatrixGraph<cpbab::Node>>().solve(&problem);
+        assert_eq!(13, graph.critical_length().unwrap());
+    }
+
+    #[test]
+    fn test_jackson_preemptive() {
+        let mut nodes = [
+            cpbab::Node::create(1, 4, Some(1), Some(0)),
+            cpbab::Node::create(2, 2, Some(1), Some(1))];
+        nodes[0].set_head(0);
+        nodes[0].set_tail(0);
+        nodes[1].set_head(1);
+        nodes[1].set_tail(5);
+
+        // Node 2 interrupts node 1 at time 1 and is done at 3 + 5, node 1 ends at 6
+        assert_eq!(8, jackson_preemptive(nodes.iter()));
+    }
 }
@@ -1,299 +1,213 @@
+//! Time windows of operations and the consistency techniques that narrow them.
+//!
+//! For a given upper bound every operation gets a window [left_bound, right_bound] in which it has to be processed.
+//! 2B consistency narrows the windows with the precedences and with every pair of operations on a machine,
+//! 3B consistency also with every triple of operations on a machine.
+use std::collections::VecDeque;
+
 use disjunctgraph::{ Graph, GraphNode, NodeId };
+use itertools::Itertools;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ConstraintError {
+    /// No schedule within the upper bound exists
     Infeasible
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Consistency {
+    TwoB,
+    ThreeB,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct ActivityConstraint {
-    pub size: u32,    
+    pub size: u32,
+    /// Earliest start
     pub left_bound: u32,
+    /// Latest completion
     pub right_bound: u32,
 }
 
+impl ActivityConstraint {
+    fn earliest_completion(&self) -> u32 {
+        self.left_bound + self.size
+    }
+
+    fn latest_start(&self) -> u32 {
+        self.right_bound - self.size
+    }
+}
+
+/// All orders of three operations
+const ORDERS: [[usize; 3]; 6] = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+
 #[derive(Clone, Debug)]
-pub struct ProblemConstraints {    
+pub struct ProblemConstraints {
     pub upper_bound: u32,
     pub constraints: Vec<ActivityConstraint>,
 }
 
 impl ProblemConstraints {
+    /// 3B consistent windows of all operations.
     pub fn new<I: Graph>(graph: &I, upper_bound: u32) -> Result<Self, ConstraintError> {
-        
-        let topology = graph.topology().collect::<Vec<&I::Node>>();
-        let nodes = graph.nodes();
-        
-        let mut constraints: Vec<ActivityConstraint> = vec!(ActivityConstraint { size: 0, left_bound: 0, right_bound: 0 }; nodes.len());
-
-        for node in topology.iter() {
-            constraints[node.id()].size = node.weight();
-            constraints[node.id()].left_bound = graph.predecessors(*node).iter()
-                    .map(|x| constraints[x.id()].left_bound + x.weight())
-                    .max().unwrap_or(0);             
-        }
+        Self::with_consistency(graph, upper_bound, Consistency::ThreeB)
+    }
 
-        for node in topology.iter().rev() {
-            let job_predecessor = graph.successors(*node).iter()
-                    .map(|x| constraints[x.id()].right_bound - x.weight())
-                    .min().unwrap_or(upper_bound);
-            
-            if job_predecessor < constraints[node.id()].left_bound + node.weight() {
-                return Err(ConstraintError::Infeasible);
-            }
-            constraints[node.id()].right_bound = job_predecessor;
+    pub fn with_consistency<I: Graph>(graph: &I, upper_bound: u32, consistency: Consistency) -> Result<Self, ConstraintError> {
+        let constraints = graph.nodes().iter()
+            .map(|node| ActivityConstraint { size: node.weight(), left_bound: 0, right_bound: upper_bound })
+            .collect::<Vec<_>>();
+
+        if constraints.iter().any(|c| c.size > upper_bound) {
+            return Err(ConstraintError::Infeasible);
         }
 
-        
         let mut problem = ProblemConstraints { upper_bound, constraints };
+        problem.propagate(graph, consistency)?;
+        Ok(problem)
+    }
 
-        
-        let left_bounded = |ccc: &mut ActivityConstraint| {
-            ccc.left_bound = (ccc.right_bound - ccc.size + ccc.left_bound) / 2;
-        };
-        let right_bounded = |ccc: &mut ActivityConstraint| {
-            ccc.right_bound = ((ccc.right_bound - ccc.size + ccc.left_bound) / 2) + ccc.size;
-        };        
-        
-        problem.fix_2b_consistency(graph);
-        problem.fix_3b_consistency(graph);        
-        problem.fix_2b_consistency(graph);
-
-        macro_rules! bound {
-            (r $i:expr) => {
-                right_bounded(&mut problem.constraints[$i])
-            };
-            (l $i:expr) => {
-                left_bounded(&mut problem.constraints[$i])
+    /// Narrow the windows until they are consistent. Every operation of which the window
+    /// changed is queued, its neighbours are revised when it is taken from the queue.
+    pub fn propagate<I: Graph>(&mut self, graph: &I, consistency: Consistency) -> Result<(), ConstraintError> {
+        let mut queue: VecDeque<usize> = (0..self.constraints.len()).collect();
+        let mut queued = vec!(true; self.constraints.len());
+        let mut changed = Vec::new();
+
+        while let Some(id) = queue.pop_front() {
+            queued[id] = false;
+
+            for successor in graph.successors(&id) {
+                self.revise_precedence(id, successor.id(), &mut changed)?;
+            }
+            for predecessor in graph.predecessors(&id) {
+                self.revise_precedence(predecessor.id(), id, &mut changed)?;
+            }
+            for disjunction in graph.disjunctions(&id) {
+                self.revise_pair(id, disjunction.id(), &mut changed)?;
             }
-        }
 
-        bound!(r 4);
-        bound!(l 5);
-        bound!(r 6);
-                
-        problem.fix_topology(graph, &topology)?;
-        problem.fix_2b_consistency(graph);        
-        
-        for ccc in problem.constraints.iter().enumerate() {            
-            println!("Constraint: {} with length: {}", ccc.0, (ccc.1.right_bound - ccc.1.left_bound));
-        }
+            if consistency == Consistency::ThreeB {
+                let machine = graph[id].machine_id();
+                let disjunctions = graph.disjunctions(&id).map(|n| n.id()).collect::<Vec<_>>();
+                for (j, k) in disjunctions.into_iter().tuple_combinations() {
+                    if graph[j].machine_id() == machine && graph[k].machine_id() == machine {
+                        self.revise_triple(graph, [id, j, k], &mut changed)?;
+                    }
+                }
+            }
 
-        fn task_interval<I: Graph>(constraints: &Vec<ActivityConstraint>, v: &Vec<&I::Node>) -> (u32, u32, u32) {
-            let min = v.iter().map(|x| constraints[x.id()].left_bound).min().unwrap();
-            let max = v.iter().map(|x| constraints[x.id()].right_bound).max().unwrap();
-            let sum = v.iter().map(|x| constraints[x.id()].size).sum::<u32>();
-            (min, max, sum)            
+            for id in changed.drain(..) {
+                if !queued[id] {
+                    queued[id] = true;
+                    queue.push_back(id);
+                }
+            }
         }
 
-        // let things = |u: usize| {
-        //     let set1 = graph.disjunctions(&nodes[u]);    
-        //     let t1 = task_interval::<I>(&problem.constraints, &set1);
-        //     println!("Packed n{}: {} - {} < {}", u, t1.1, t1.0, t1.2 + nodes[u].weight());
-
-        //     println!("is_after n{}: {} > max:", 
-        //         u, 
-        //         problem.constraints[u].left_bound + nodes[u].weight()
-        //     );
-        //     for n in set1 {
-        //         println!("{}, ", problem.constraints[n.id()].right_bound - n.weight());
-        //     }
-        // };
-
-        // things(2);
-        // things(4);
-        // things(3);
-        // For fun:graph.fix_disjunction(disjunction, node).expect("Could not fix");
-
-        nodes.into_iter()
-            .map(|x| graph.disjunctions(x).into_iter().map(move |y| (x, y)))
-            .flatten()
-            .filter(|&(x, y)| !problem.check_precedence(x, y))            
-            .map(|(x, y)| (y.id(), x.id()))
-            .for_each(|(x, y)| println!("Found dependency: {} -> {}", x, y));
-
-        Ok(problem)
+        Ok(())
     }
 
-    fn fix_topology<I: Graph>(&mut self, graph: &I, topology: &Vec<&I::Node>) -> Result<(), ConstraintError> {
-        for node in topology.iter() {            
-            
-            let job_pre = graph.predecessors(*node).iter()
-                    .map(|x| self.constraints[x.id()].left_bound + x.weight())                    
-                    .max().unwrap_or(0);
-
-            self.constraints[node.id()].left_bound = std::cmp::max(self.constraints[node.id()].left_bound, job_pre);
+    /// Narrow the window of an operation, errors when it becomes empty.
+    fn tighten(&mut self, id: usize, left_bound: u32, right_bound: u32, changed: &mut Vec<usize>) -> Result<(), ConstraintError> {
+        let constraint = &mut self.constraints[id];
+        if left_bound <= constraint.left_bound && right_bound >= constraint.right_bound {
+            return Ok(());
         }
 
-        for node in topology.iter().rev() {
-            let job_predecessor = graph.successors(*node).iter()
-                    .map(|x| self.constraints[x.id()].right_bound - x.weight())
-                    .min().unwrap_or(self.constraints[node.id()].right_bound);
-            
-            if job_predecessor < self.constraints[node.id()].left_bound + node.weight() {
-                return Err(ConstraintError::Infeasible);
-            }
-            self.constraints[node.id()].right_bound = std::cmp::min(self.constraints[node.id()].right_bound, job_predecessor);
+        let left_bound = std::cmp::max(left_bound, constraint.left_bound);
+        let right_bound = std::cmp::min(right_bound, constraint.right_bound);
+        if left_bound + constraint.size > right_bound {
+            return Err(ConstraintError::Infeasible);
         }
 
+        constraint.left_bound = left_bound;
+        constraint.right_bound = right_bound;
+        changed.push(id);
         Ok(())
     }
 
-    pub fn score(&self) -> f32 {
-        self.constraints.iter().map(|ccc| {
-            // Maximize overlap? Nope, it works kind of
-            //let overlap = ((ccc.left_bound + ccc.size) - (ccc.right_bound - ccc.size)) as f32;// Not exactly
-
-            // Estimating a makespan would work as. 
-            //let bbb = (self.upper_bound - (ccc.right_bound - ccc.left_bound)) as f32;
-            ccc.right_bound as f32
-        }).sum()
+    /// Precedence node_1 -> node_2
+    fn revise_precedence(&mut self, node_1: usize, node_2: usize, changed: &mut Vec<usize>) -> Result<(), ConstraintError> {
+        let earliest_completion = self.constraints[node_1].earliest_completion();
+        self.tighten(node_2, earliest_completion, u32::MAX, changed)?;
+        let latest_start = self.constraints[node_2].latest_start();
+        self.tighten(node_1, 0, latest_start, changed)
     }
 
-    fn fix_2b_consistency<I: Graph>(&mut self, graph: &I) {
-        let nodes = graph.nodes();
-        
-        //dbg!("This is inefficient, fix_2b_consistency");
-        while nodes.iter().map(|node| self.fix_2b_consistency_node(graph, node)).any(|x| x) {
-            // Do nothing,
-        }        
+    /// Two operations on a machine, if one order does not fit the other one has to be taken.
+    fn revise_pair(&mut self, node_1: usize, node_2: usize, changed: &mut Vec<usize>) -> Result<(), ConstraintError> {
+        match (self.fits(node_1, node_2), self.fits(node_2, node_1)) {
+            (true, true) => Ok(()),
+            (true, false) => self.revise_precedence(node_1, node_2, changed),
+            (false, true) => self.revise_precedence(node_2, node_1, changed),
+            (false, false) => Err(ConstraintError::Infeasible)
+        }
     }
 
-    fn fix_2b_consistency_node<I: Graph>(&mut self, graph: &I, node: &I::Node) -> bool {
-        let mut changed = false;
-
-        for disjunction in graph.disjunctions(node) {
-            let p_j = disjunction.weight();
-            let lst_j = self.constraints[disjunction.id()].right_bound - p_j;
-            let est_j = self.constraints[disjunction.id()].left_bound;
+    /// Three operations on a machine. Every operation can start no earlier than its earliest start
+    /// in any order that fits, and complete no later than its latest completion in any order that fits.
+    fn revise_triple<I: Graph>(&mut self, graph: &I, nodes: [usize; 3], changed: &mut Vec<usize>) -> Result<(), ConstraintError> {
+        let mut left_bounds = [u32::MAX; 3];
+        let mut right_bounds = [0; 3];
 
-            // Node -> disjunction not possible.
-            // Has to be disjunction -> node
-            if !self.check_precedence(node, disjunction) && self.constraints[node.id()].left_bound < est_j + p_j {
-                self.constraints[node.id()].left_bound = est_j + p_j;
-                changed = true;
+        for order in &ORDERS {
+            // Orders against fixed precedences are not possible
+            if order.iter().tuple_combinations().any(|(a, b)| graph.has_precedence(&nodes[*b], &nodes[*a])) {
+                continue;
             }
 
-            // Disjunction -> node not possible,
-            // has to be node -> disjunction
-            if !self.check_precedence(disjunction, node) && self.constraints[node.id()].right_bound > lst_j {
-                self.constraints[node.id()].right_bound = lst_j;
-                changed = true;
+            let mut starts = [0; 3];
+            let mut time = 0;
+            for &i in order {
+                starts[i] = std::cmp::max(time, self.constraints[nodes[i]].left_bound);
+                time = starts[i] + self.constraints[nodes[i]].size;
+            }
+            if order.iter().any(|&i| starts[i] + self.constraints[nodes[i]].size > self.constraints[nodes[i]].right_bound) {
+                continue;
             }
-        }
-
-        changed
-    }
-
-    fn fix_3b_consistency<I: Graph>(&mut self, graph: &I) {
-        let nodes = graph.nodes();
 
-        
-        let mut should_continue = true;
-        //dbg!("This is inefficient, fix_3b_consistency");
-        while should_continue {            
-            should_continue = nodes.iter().map(|node| self.fix_3b_consistency_node(graph, node)).any(|x| x);
+            let mut time = u32::MAX;
+            for &i in order.iter().rev() {
+                let completion = std::cmp::min(time, self.constraints[nodes[i]].right_bound);
+                right_bounds[i] = std::cmp::max(right_bounds[i], completion);
+                left_bounds[i] = std::cmp::min(left_bounds[i], starts[i]);
+                time = completion - self.constraints[nodes[i]].size;
+            }
         }
-    }
-
-    // Assuming that it is 2b-consistent
-    fn fix_3b_consistency_node<I: Graph>(&mut self, graph: &I, node: &I::Node) -> bool {
-        let mut changed = false;  
-        for j in graph.disjunctions(node) {
-            for k in graph.disjunctions(node) {
-                if j.id() > k.id() {
-                    let tests = self.tests::<I>(node, j, k);
-
-                    let (p_i, p_j, p_k) = (node.weight(), j.weight(), k.weight());
-                        let est_i = self.constraints[node.id()].left_bound;
-                        let est_j = self.constraints[j.id()].left_bound;
-                        let est_k = self.constraints[k.id()].left_bound;
-
-                        let lct_i = self.constraints[node.id()].right_bound;                        
-                        let lct_j = self.constraints[j.id()].right_bound;
-                        let lct_k = self.constraints[k.id()].right_bound;
-
-                    if !tests.1 {
-                        let mut next_value = lct_i;
-                        // First, if jik can be satisfied...
-                        let jik = (est_i <= est_j + p_j) & (est_j + p_j + p_i + p_k <= lct_k)
-                            & (lct_k <= lct_i + p_k) & (lct_k - p_k < next_value);
-                        let kij = (est_i <= est_k + p_k) & (est_k + p_k + p_i + p_j <= lct_j)
-                            & (lct_j <= lct_i + p_j) & (lct_j - p_j < next_value);
-                        
-                        // This should be wrong. This takes the assumption that jik or kij will be satisfied before jki or kji
-                        if jik {
-                            next_value = lct_k - p_k;
-                            changed = true;
-                        } else if kij {
-                            next_value = lct_j - p_j;
-                            changed = true;
-                        } else {
-                            let path = std::cmp::min(est_j + p_j + p_k, est_k + p_k + p_j); // jki or kji
-                            if path > next_value {
-                                changed = true;
-                                next_value = path;
-                            }
-                        }
-                        self.constraints[node.id()].right_bound = next_value;
-                    }
 
-                    if !tests.0 {
-                        let mut next_value = est_i;
-                        // First, if jik can be satisfied...
-                        if  (est_i <= est_j + p_j) & (est_j + p_j + p_i + p_k <= lct_k)
-                            & (lct_k <= lct_i + p_k) & (est_j + p_j > next_value) {
-                            next_value = est_j + p_j;
-                            changed = true;    
-                        // Else if kij can be satisfied...
-                        } else if  (est_i <= est_k + p_k) & (est_k + p_k + p_i + p_j <= lct_j)
-                            & (lct_j <= lct_i + p_j) & (est_k + p_k > next_value) {
-                            next_value = est_j + p_j;
-                            changed = true;    
-                        } else {
-                            let path = std::cmp::min(est_j + p_j + p_k, est_k + p_k + p_j); // jki or kji
-                            if path > next_value {
-                                changed = true;
-                                next_value = path;
-                            }
-                        }
-                        self.constraints[node.id()].left_bound = dbg!(next_value);
-                                                
-                    }
-                }
+        for i in 0..3 {
+            if left_bounds[i] == u32::MAX {
+                return Err(ConstraintError::Infeasible);
             }
+            self.tighten(nodes[i], left_bounds[i], right_bounds[i], changed)?;
         }
+        Ok(())
+    }
 
-        changed
-    }    
-    // is it i->etc or etc->i
-    fn tests<I: Graph>(&mut self, node: &I::Node, j: &I::Node, k: &I::Node) -> (bool, bool) {        
-        
-        let ji = self.check_precedence(j, node);
-        let ki = self.check_precedence(k, node);
-
-        let ij = self.check_precedence(node, j);
-        let ik = self.check_precedence(node, k);
-
-        let jk = self.check_precedence(j, k);
-        let kj = self.check_precedence(k, j);
+    /// Operation node_1 can be processed before node_2 within their windows.
+    fn fits(&self, node_1: usize, node_2: usize) -> bool {
+        self.constraints[node_1].earliest_completion() + self.constraints[node_2].size <= self.constraints[node_2].right_bound
+    }
 
-        ((ij & jk) | (ik & kj), (kj & ji) | (jk & ki))
+    /// Sum of the latest completions, smaller is tighter.
+    pub fn score(&self) -> f32 {
+        self.constraints.iter().map(|ccc| ccc.right_bound as f32).sum()
     }
 
-    pub fn check_2b_precedence<I:Graph>(&self, graph: &I) -> bool {
-        graph.nodes().into_iter()
-            .map(|node| graph.disjunctions(node).into_iter().map(move |o| (node, o)))
-            .flatten()
-            .all(|(i, j)| {
-                self.check_precedence(i, j) | self.check_precedence(j, i)                
-            })
+    /// Every unfixed pair of operations can be ordered in some way.
+    pub fn check_2b_precedence<I: Graph>(&self, graph: &I) -> bool {
+        graph.nodes().iter()
+            .flat_map(|node| graph.disjunctions(node).map(move |o| (node, o)))
+            .all(|(i, j)| self.check_precedence(i, j) | self.check_precedence(j, i))
     }
 
+    /// Every unfixed triple of operations can be ordered in some way.
     pub fn check_3b_precedence<I: Graph>(&self, graph: &I) -> bool {
-        graph.nodes().into_iter()
-            .flat_map(|node| graph.disjunctions(node).into_iter().map(move |o| (node, o)))
-            .flat_map(|(node, a)| graph.disjunctions(node).into_iter().map(move |o| (node, a, o)))
+        graph.nodes().iter()
+            .flat_map(|node| graph.disjunctions(node).map(move |o| (node, o)))
+            .flat_map(|(node, a)| graph.disjunctions(node).map(move |o| (node, a, o)))
             .filter(|(_, j, k)| j.id() > k.id())
             .all(|(i, j, k)| {
                 let ij = self.check_precedence(i, j);
@@ -310,11 +224,60 @@ impl ProblemConstraints {
                 (ki & ij) | (kj & ji)
             })
     }
-    
+
+    /// Check if arc node_1 -> node_2 is possible.
+    /// Meaning the earliest end date of node_1 has to be before the latest start date of node_2
     pub fn check_precedence(&self, node_1: &impl GraphNode, node_2: &impl GraphNode) -> bool {
-        // Check if arc node_1 -> node_2 is possible.
-        // Meaning the earliest end date of node_1 has to be before the latest start date of node_2      
+        self.fits(node_1.id(), node_2.id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem::{ Problem, ProblemNode };
+    use disjunctgraph::LinkedGraph;
+
+    fn graph(problem: &str) -> LinkedGraph<ProblemNode> {
+        Problem::from_reader(problem.as_bytes()).unwrap().into_graph()
+    }
+
+    #[test]
+    fn test_two_b_windows() {
+        // Job 1 takes 3 on machine 1 and 2 on machine 2, job 2 takes 4 on machine 1
+        let graph = graph(r"2
+2
+7
+3 2
+4
+1 2
+1");
+        let problem = ProblemConstraints::with_consistency(&graph, 7, Consistency::TwoB).unwrap();
+
+        // Job 2 can not go first on machine 1
+        let windows = problem.constraints[1..4].iter().map(|c| (c.left_bound, c.right_bound)).collect::<Vec<_>>();
+        assert_eq!(vec!((0, 3), (3, 7), (3, 7)), windows);
+        assert!(problem.check_2b_precedence(&graph));
+
+        assert_eq!(Err(ConstraintError::Infeasible), ProblemConstraints::with_consistency(&graph, 6, Consistency::TwoB).map(|_| ()));
+    }
 
-        self.constraints[node_1.id()].left_bound + node_1.weight() + node_2.weight() <= self.constraints[node_2.id()].right_bound
+    #[test]
+    fn test_three_b_infeasible() {
+        // Every pair fits in 8, the three operations do not
+        let graph = graph(r"3
+1
+9
+2
+3
+4
+1
+1
+1");
+        assert!(ProblemConstraints::with_consistency(&graph, 8, Consistency::TwoB).is_ok());
+        assert_eq!(Err(ConstraintError::Infeasible), ProblemConstraints::new(&graph, 8).map(|_| ()));
+
+        let problem = ProblemConstraints::new(&graph, 9).unwrap();
+        assert!(problem.check_3b_precedence(&graph));
     }
-}
\ No newline at end of file
+}
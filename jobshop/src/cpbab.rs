@@ -1,31 +1,73 @@
 use crate::problem::{ ProblemSolver, Problem };
-use disjunctgraph::Graph;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use disjunctgraph::{ ConstrainedNode, Graph };
 
 // In the case of a search, it might be nice to only store partial orientations.
 // As described in https://pure.tue.nl/ws/files/2119953/385216.pdf
-pub struct CPBAB;
+pub struct CPBAB<G = cpbab::CGraph> {
+    config: cpbab::SearchConfig,
+    bidirectional: bool,
+    graph: PhantomData<fn() -> G>,
+}
 
 impl CPBAB {
     pub fn new() -> Self {
-        CPBAB {}
+        CPBAB::with_config(Default::default())
+    }
+
+    pub fn with_config(config: cpbab::SearchConfig) -> Self {
+        CPBAB { config, bidirectional: false, graph: PhantomData }
     }
 }
-impl ProblemSolver for CPBAB {
-    type Solution = cpbab::CGraph;
+
+impl<G> CPBAB<G> {
+    /// Search with another graph representation.
+    pub fn with_graph<H>(self) -> CPBAB<H> {
+        CPBAB { config: self.config, bidirectional: self.bidirectional, graph: PhantomData }
+    }
+
+    /// Race the search against the search on the reversed problem.
+    /// Both searches would record into the same tree and write the same checkpoint file,
+    /// so the race runs without a recorded search tree and without checkpointing.
+    pub fn bidirectional(self) -> Self {
+        CPBAB { bidirectional: true, ..self }
+    }
+
+    pub fn config(&self) -> &cpbab::SearchConfig {
+        &self.config
+    }
+}
+impl<G: Graph + Clone + Debug + Send + 'static> ProblemSolver for CPBAB<G> where G::Node: ConstrainedNode + Debug {
+    type Solution = G;
 
     fn solve(&self, problem: &Problem) -> Self::Solution {
+        if self.bidirectional {
+            let config = cpbab::SearchConfig { record: None, checkpoint: None, ..self.config.clone() };
+            return crate::bidirectional::race(problem, move |problem, stop| {
+                CPBAB::with_config(cpbab::SearchConfig { stop: Some(stop), ..config.clone() }).with_graph::<G>().solve(problem)
+            });
+        }
+
         let mm = crate::local_search::LocalSearch::new(5000)
             .solve(problem)
             .critical_length().unwrap();
-        let graph = problem.into_graph();
+        let graph: G = problem.into_graph();
 
         println!("Found local search: {}", mm);
         
-        let solution = cpbab::branch_and_bound(graph, problem.machines as usize, 578);//found 579; // found 573
-
-        println!("dbg: {:?}", solution);
-
-        solution
+        // The local search schedule has makespan mm, so there is always a schedule within it.
+        match cpbab::branch_and_bound_with(graph.clone(), problem.machines as usize, mm, &self.config) {
+            Ok(solution) => {
+                println!("{}", solution.stats);
+                solution.graph
+            },
+            Err(e) => {
+                println!("{}", e);
+                graph
+            }
+        }
 
     }
 }
@@ -34,7 +76,7 @@ impl ProblemSolver for CPBAB {
 #[cfg(test)]
 mod tests {    
     use crate::cpbab::CPBAB;
-    use crate::problem::{ Problem, ProblemSolver };
+    use crate::problem::{ Problem, ProblemSolver, small_problem };
     use test::Bencher;
 
     #[test]
@@ -74,6 +116,193 @@ mod tests {
         assert_eq!(589, l.critical_length().unwrap());
     }
 
+    #[test]
+    fn test_cpbab_implied() {
+        use disjunctgraph::Graph;
+        let config = ::cpbab::SearchConfig { implied: true, ..Default::default() };
+        let l = CPBAB::with_config(config).solve(&small_problem());
+        assert_eq!(13, l.critical_length().unwrap());
+    }
+
+    #[test]
+    fn test_cpbab_matrix_graph() {
+        use disjunctgraph::{ Graph, MatrixGraph };
+        let l = CPBAB::new().with_graph::<MatrixGraph<::cpbab::Node>>().solve(&small_problem());
+        assert!(!l.has_disjunctions());
+        assert_eq!(13, l.critical_length().unwrap());
+    }
+
+    #[test]
+    fn test_cpbab_bit_graph() {
+        use disjunctgraph::{ Graph, BitGraph };
+        let l = CPBAB::new().with_graph::<BitGraph<::cpbab::Node>>().solve(&small_problem());
+        assert!(!l.has_disjunctions());
+        assert_eq!(13, l.critical_length().unwrap());
+    }
+
+    #[test]
+    fn test_cpbab_restarts() {
+        use disjunctgraph::Graph;
+        use ::cpbab::{ Restarts, SearchConfig };
+        let config = SearchConfig { seed: Some(7), restarts: Some(Restarts::Luby(1)), ..Default::default() };
+        let solution = ::cpbab::branch_and_bound_with(small_problem().into_graph::<::cpbab::CGraph>(), 3, 20, &config).unwrap();
+        assert_eq!(Some(13), solution.makespan);
+        assert_eq!(13, solution.graph.critical_length().unwrap());
+        assert!(solution.stats.runs > 1);
+        assert!(solution.stats.solutions > 0);
+        assert!(solution.stats.nodes >= solution.stats.runs);
+    }
+
+    #[test]
+    fn test_cpbab_checkpoint() {
+        use disjunctgraph::Graph;
+        use ::cpbab::{ Checkpoint, Checkpointing, SearchConfig };
+        let problem = small_problem();
+        let root: ::cpbab::CGraph = problem.into_graph();
+        let path = std::env::temp_dir().join("test_cpbab_checkpoint.txt");
+
+        let config = SearchConfig { checkpoint: Some(Checkpointing { path: path.clone(), interval: 2 }), ..Default::default() };
+        let solution = ::cpbab::branch_and_bound_with(root.clone(), 3, 20, &config).unwrap().graph;
+        let checkpoint = Checkpoint::read(&path).unwrap();
+        assert!(checkpoint.node_evaluations >= 2);
+
+        let mut buffer = Vec::new();
+        checkpoint.write(&mut buffer).unwrap();
+        assert_eq!(checkpoint, Checkpoint::from_reader(&buffer[..]).unwrap());
+        // A line that can not be read is an error, it is not skipped
+        let mut corrupt = buffer.clone();
+        corrupt.extend_from_slice(b"open \xff\n");
+        assert!(Checkpoint::from_reader(&corrupt[..]).is_err());
+
+        let resumed = ::cpbab::resume(root.clone(), 3, &checkpoint, &SearchConfig::default()).unwrap();
+        assert!(resumed.stats.nodes > checkpoint.node_evaluations);
+        let resumed = resumed.graph;
+        assert!(!resumed.has_disjunctions());
+        assert_eq!(solution.critical_length().unwrap(), resumed.critical_length().unwrap());
+        std::fs::remove_file(path).unwrap();
+
+        // A checkpoint that can not be written stops the search
+        let path = std::env::temp_dir().join("missing").join("test_cpbab_checkpoint.txt");
+        let config = SearchConfig { checkpoint: Some(Checkpointing { path, interval: 2 }), ..Default::default() };
+        assert!(::cpbab::branch_and_bound_with(root, 3, 20, &config).is_err());
+    }
+
+    #[test]
+    fn test_cpbab_resume_identical() {
+        use ::cpbab::{ Checkpoint, Checkpointing, GraphState, Restarts, SearchConfig };
+        let root: ::cpbab::CGraph = uniform_problem().into_graph();
+        let path = std::env::temp_dir().join("test_cpbab_resume_identical.txt");
+
+        // Random tie-breaking and restarts continue after a resume as they would have without the interruption.
+        // Equal processing times give many ties, a generator that starts again from the seed explores another tree.
+        let config = SearchConfig {
+            seed: Some(15), restarts: Some(Restarts::Luby(2)),
+            checkpoint: Some(Checkpointing { path: path.clone(), interval: 5 }),
+            ..Default::default()
+        };
+        let solution = ::cpbab::branch_and_bound_with(root.clone(), 4, 16, &config).unwrap();
+        let checkpoint = Checkpoint::read(&path).unwrap();
+        let resumed = ::cpbab::resume(root, 4, &checkpoint, &config).unwrap();
+        assert_eq!(solution.stats.nodes, resumed.stats.nodes);
+        assert_eq!(solution.stats.runs, resumed.stats.runs);
+        assert_eq!(GraphState::new(&solution.graph), GraphState::new(&resumed.graph));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_cpbab_nogoods() {
+        use ::cpbab::{ Restarts, SearchConfig };
+        let problem = medium_problem();
+        let root: ::cpbab::CGraph = problem.into_graph();
+
+        // Restarts explore the same decisions again, nogoods skip the fixations that failed before.
+        // Explaining failures costs propagations too, together they have to cost less.
+        let config = SearchConfig { seed: Some(1), restarts: Some(Restarts::Luby(16)), ..Default::default() };
+        let without = ::cpbab::branch_and_bound_with(root.clone(), 5, 45, &config).unwrap();
+        let with = ::cpbab::branch_and_bound_with(root, 5, 45, &SearchConfig { nogoods: 1000, ..config }).unwrap();
+        assert_eq!(Some(44), without.makespan);
+        assert_eq!(Some(44), with.makespan);
+        assert!(with.stats.nogoods > 0);
+        assert!(with.stats.propagations < without.stats.propagations);
+    }
+
+    #[test]
+    fn test_cpbab_search_tree() {
+        use disjunctgraph::Graph;
+        use ::cpbab::{ NodeOutcome, SearchConfig, SearchTree };
+        use std::sync::{ Arc, Mutex };
+        let tree = Arc::new(Mutex::new(SearchTree::new()));
+        // A loose bound, so the search has to branch
+        let config = SearchConfig { record: Some(tree.clone()), ..Default::default() };
+        let l = ::cpbab::branch_and_bound_with(small_problem().into_graph::<::cpbab::CGraph>(), 3, 20, &config).unwrap().graph;
+        assert_eq!(13, l.critical_length().unwrap());
+
+        let tree = tree.lock().unwrap();
+        assert_eq!(1, tree.nodes().iter().filter(|node| node.parent.is_none()).count());
+        assert!(tree.nodes().iter().enumerate().all(|(id, node)| node.parent.is_none_or(|parent| parent < id)));
+        assert!(tree.count(NodeOutcome::Solution(13)) > 0);
+        assert_eq!(0, tree.count(NodeOutcome::Open));
+
+        let mut json = Vec::new();
+        tree.write_json(&mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(tree.len(), json["nodes"].as_array().unwrap().len());
+        assert_eq!("branched", json["nodes"][0]["outcome"]);
+
+        let mut dot = Vec::new();
+        tree.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph search {"));
+        assert_eq!(tree.len() - 1, dot.matches(" -> n").count());
+    }
+
+    #[test]
+    fn test_cpbab_replay() {
+        use disjunctgraph::Graph;
+        use ::cpbab::{ NodeOutcome, SearchConfig, SearchTree, Step };
+        use std::sync::{ Arc, Mutex };
+        let problem = small_problem();
+        let tree = Arc::new(Mutex::new(SearchTree::new()));
+        let config = SearchConfig { record: Some(tree.clone()), ..Default::default() };
+        ::cpbab::branch_and_bound_with(problem.into_graph::<::cpbab::CGraph>(), 3, 20, &config).unwrap();
+
+        let tree = tree.lock().unwrap();
+        let (id, node) = tree.nodes().iter().enumerate().find(|(_, node)| node.outcome == NodeOutcome::Solution(13)).unwrap();
+        let steps = tree.decisions(id).into_iter().map(Step::from).collect::<Vec<_>>();
+        assert_eq!(steps, ::cpbab::parse_path(&::cpbab::format_path(&steps)).unwrap());
+
+        let root: ::cpbab::CGraph = problem.into_graph();
+        let graph = ::cpbab::replay(root.clone(), &steps, node.upper_bound, &SearchConfig::default()).unwrap();
+        assert!(!graph.has_disjunctions());
+        assert_eq!(13, graph.critical_length().unwrap());
+
+        // Reversing the first decision after taking it fails on that step
+        let (a, b) = tree.decisions(id)[0];
+        let failure = ::cpbab::replay(root, &[Step::Fix(a, b), Step::Tail(a, 2), Step::Fix(b, a)], 13, &SearchConfig::default()).unwrap_err();
+        assert_eq!(Some(2), failure.step);
+        assert!(failure.graph.has_precedence(&a, &b));
+        assert_eq!(vec!(Step::Head(4, 10), Step::Tail(4, 2)), ::cpbab::parse_path("h4=10 t4=2").unwrap());
+    }
+
+    #[test]
+    fn test_find_orders() {
+        use disjunctgraph::{ Graph, GraphError };
+        let problem = debug_problem();
+        let mut graph: ::cpbab::CGraph = problem.into_graph();
+        graph.init_weights();
+
+        // On machine 2, node 2 -> node 4 would take 2 + 7 + 4 + 3 = 16
+        let deductions = disjunctgraph::find_orders(&[1, 2], &graph, 13).unwrap();
+        assert!(deductions.orders.contains(&(4, 2)));
+        assert!(!deductions.orders.contains(&(2, 4)));
+
+        // and node 4 -> node 2 takes 0 + 4 + 7 + 2 = 13
+        match disjunctgraph::find_orders(&[1, 2], &graph, 12) {
+            Err(GraphError::Infeasible) => (),
+            other => panic!("Expected infeasible, got {:?}", other)
+        }
+    }
+
     /*#[bench]
     fn bench_local_search_small(b: &mut Bencher) {
         use crate::local_search::LocalSearch;
@@ -101,16 +330,37 @@ mod tests {
 1 2 1
 2 1".as_bytes()).unwrap()
     }
-    fn small_problem() -> Problem {
-        Problem::from_reader(r"3
-3
-13
-3 2 3
-3 4
-6 3 2
-1 2 3
-3 2
-2 1 3".as_bytes()).unwrap()
+    /// Every operation takes as long, so the branching has many ties
+    fn uniform_problem() -> Problem {
+        Problem::from_reader(r"4
+4
+8
+2 2 2 2
+2 2 2 2
+2 2 2 2
+2 2 2 2
+1 2 3 4
+2 1 4 3
+3 4 1 2
+4 3 2 1".as_bytes()).unwrap()
+    }
+
+    fn medium_problem() -> Problem {
+        Problem::from_reader(r"6
+5
+44
+4 5 2 7 8
+3 2 2 1 7
+9 5 1 4 9
+9 6 5 3 2
+5 4 1 5 5
+4 3 5 5 6
+5 2 4 3 1
+4 3 1 2 5
+5 2 1 3 4
+5 2 4 3 1
+1 3 4 2 5
+1 4 2 3 5".as_bytes()).unwrap()
     }
 
     fn big_problem() -> Problem {
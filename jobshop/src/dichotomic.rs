@@ -0,0 +1,183 @@
+use std::fmt::Debug;
+
+use disjunctgraph::{ ConstrainedNode, Graph };
+
+use crate::problem::{ ProblemSolver, Problem };
+use crate::cpbab::CPBAB;
+use crate::playbab::PlayBAB;
+
+/// Answer of a solver to: is there a schedule with a makespan of at most some value?
+pub use cpbab::Outcome;
+
+/// Solvers that can answer the decision version of the problem within a budget of search nodes
+pub trait DecisionSolver {
+    type Graph;
+    /// The answer and the number of node evaluations it took
+    fn decide(&self, problem: &Problem, makespan: u32, budget: usize) -> (Outcome<Self::Graph>, usize);
+}
+
+impl<G: Graph + Clone + Debug> DecisionSolver for CPBAB<G> where G::Node: ConstrainedNode + Debug {
+    type Graph = G;
+
+    fn decide(&self, problem: &Problem, makespan: u32, budget: usize) -> (Outcome<Self::Graph>, usize) {
+        let (outcome, stats) = cpbab::decide(problem.into_graph(), problem.machines as usize, makespan, budget, self.config());
+        (outcome, stats.nodes)
+    }
+}
+
+impl<G: Graph + Clone + Debug> DecisionSolver for PlayBAB<G> where G::Node: ConstrainedNode + Debug {
+    type Graph = G;
+
+    fn decide(&self, problem: &Problem, makespan: u32, budget: usize) -> (Outcome<Self::Graph>, usize) {
+        let (outcome, stats) = playbab::decide(problem.into_graph(), makespan, budget, self.config());
+        let outcome = match outcome {
+            playbab::Outcome::Feasible(graph, length) => Outcome::Feasible(graph, length),
+            playbab::Outcome::Infeasible => Outcome::Infeasible,
+            playbab::Outcome::Unknown => Outcome::Unknown,
+        };
+        (outcome, stats.nodes)
+    }
+}
+
+/// Which makespan is tried next
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Strategy {
+    /// Halve the range between the lower bound and the best makespan.
+    Bisection,
+    /// Always ask for one less than the best makespan, stops when that is not answered.
+    Decreasing,
+}
+
+/// One call to the decision solver, neither found nor infeasible when the budget ran out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Probe {
+    /// The asked makespan
+    pub makespan: u32,
+    /// Makespan of the schedule that was found
+    pub found: Option<u32>,
+    /// There is no schedule within the asked makespan
+    pub infeasible: bool,
+    pub nodes: usize,
+}
+
+/// Best schedule found and the proven lower bound, optimal when the two meet
+pub struct Bounds<G> {
+    pub best: Option<(G, u32)>,
+    pub lower_bound: u32,
+    /// Every call to the decision solver, in order
+    pub probes: Vec<Probe>,
+}
+
+impl<G> Bounds<G> {
+    pub fn optimal(&self) -> bool {
+        matches!(&self.best, Some((_, makespan)) if *makespan == self.lower_bound)
+    }
+}
+
+/// Searches over the makespan with repeated calls to the decision version of a solver.
+/// Every feasible answer improves the upper bound, every infeasible answer the lower bound.
+pub struct Dichotomic<S> {
+    solver: S,
+    strategy: Strategy,
+    budget: usize,
+    bounds: Option<(u32, u32)>,
+}
+
+impl<S: DecisionSolver> Dichotomic<S> {
+    /// `budget` is the number of search nodes of every call.
+    pub fn new(solver: S, strategy: Strategy, budget: usize) -> Self {
+        Dichotomic { solver, strategy, budget, bounds: None }
+    }
+
+    /// Search within [lower, upper] instead of the trivial bounds of the problem.
+    pub fn with_bounds(self, lower: u32, upper: u32) -> Self {
+        Dichotomic { bounds: Some((lower, upper)), ..self }
+    }
+}
+
+impl<S: DecisionSolver> ProblemSolver for Dichotomic<S> {
+    type Solution = Bounds<S::Graph>;
+
+    fn solve(&self, problem: &Problem) -> Self::Solution {
+        let (mut lower_bound, upper) = self.bounds.unwrap_or_else(|| trivial_bounds(problem));
+
+        let mut best: Option<(S::Graph, u32)> = None;
+        let mut probes = Vec::new();
+        // Makespans of at least `low` have not been shown infeasible or given up on
+        let mut low = lower_bound;
+        let mut high = upper;
+
+        while low <= high {
+            let makespan = match self.strategy {
+                Strategy::Bisection => low + (high - low) / 2,
+                Strategy::Decreasing => high,
+            };
+
+            let (outcome, nodes) = self.solver.decide(problem, makespan, self.budget);
+            probes.push(Probe {
+                makespan,
+                found: match &outcome { Outcome::Feasible(_, length) => Some(*length), _ => None },
+                infeasible: matches!(outcome, Outcome::Infeasible),
+                nodes,
+            });
+
+            match outcome {
+                Outcome::Feasible(graph, length) => {
+                    best = Some((graph, length));
+                    match length.checked_sub(1) {
+                        Some(bound) => high = bound,
+                        None => break
+                    }
+                },
+                Outcome::Infeasible => {
+                    lower_bound = std::cmp::max(lower_bound, makespan + 1);
+                    low = makespan + 1;
+                },
+                Outcome::Unknown => {
+                    match self.strategy {
+                        // Try larger makespans, which are easier to answer
+                        Strategy::Bisection => low = makespan + 1,
+                        Strategy::Decreasing => break,
+                    }
+                }
+            }
+        }
+
+        Bounds { best, lower_bound, probes }
+    }
+}
+
+/// The longest job or machine load, and the sum of all processing times.
+fn trivial_bounds(problem: &Problem) -> (u32, u32) {
+    let jobs = problem.jobs.iter()
+        .map(|job| job.iter().map(|id| problem.activities[*id].process_time).sum::<u32>());
+    let machines = (1..=problem.machines)
+        .map(|m| problem.activities.iter().filter(|a| a.machine_id == m).map(|a| a.process_time).sum::<u32>());
+
+    let lower = jobs.chain(machines).max().unwrap_or(0);
+    let upper = problem.activities.iter().map(|a| a.process_time).sum();
+    (lower, upper)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem::small_problem;
+
+    #[test]
+    fn test_dichotomic() {
+        let problem = small_problem();
+        for strategy in &[Strategy::Bisection, Strategy::Decreasing] {
+            let bounds = Dichotomic::new(CPBAB::new(), *strategy, 10_000).solve(&problem);
+            assert!(bounds.optimal());
+            assert_eq!(13, bounds.lower_bound);
+            assert!(bounds.probes.iter().any(|probe| probe.found == Some(13)));
+            let last = bounds.probes.last().unwrap();
+            assert_eq!((12, None, true), (last.makespan, last.found, last.infeasible));
+
+            let bounds = Dichotomic::new(PlayBAB::new(), *strategy, 10_000).solve(&problem);
+            assert!(bounds.optimal());
+            assert_eq!(13, bounds.lower_bound);
+        }
+    }
+}
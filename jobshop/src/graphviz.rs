@@ -0,0 +1,205 @@
+//! Graphviz DOT output of a disjunctive graph, to look at search states outside the GUI.
+//!
+//! Precedences are solid arcs and the disjunctions left are dashed lines. Nodes are colored by machine
+//! and the operations of the jobs are laid out in rows, the n-th operation of every job in the same column.
+use std::borrow::Cow;
+use std::io::{ self, Write };
+
+use disjunctgraph::{ ConstrainedNode, Graph, GraphError, GraphNode, NodeId };
+use dot::{ Arrow, Edges, GraphWalk, Id, LabelText, Labeller, Nodes, Style };
+use hashbrown::HashSet;
+
+const COLORS: [&str; 10] = ["lightblue", "lightpink", "palegreen", "khaki", "plum", "lightsalmon", "lightcyan", "wheat", "thistle", "aquamarine"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdgeKind {
+    Precedence,
+    Disjunction
+}
+
+pub type Edge = (usize, usize, EdgeKind);
+
+pub struct DotGraph<'g, G: Graph> {
+    graph: &'g G,
+    labels: Vec<String>,
+    critical: HashSet<(usize, usize)>,
+}
+
+impl<'g, G: Graph> DotGraph<'g, G> {
+    /// Nodes are labelled with their id and processing time
+    pub fn new(graph: &'g G) -> Self {
+        let labels = graph.nodes().iter()
+            .map(|node| format!("{}\np: {}", node.id(), node.weight()))
+            .collect();
+
+        DotGraph { graph, labels, critical: HashSet::new() }
+    }
+
+    /// Also label the nodes with their heads and tails
+    pub fn with_windows(mut self) -> Self where G::Node: ConstrainedNode {
+        self.labels = self.graph.nodes().iter()
+            .map(|node| format!("{}\np: {}\nhead: {}, tail: {}", node.id(), node.weight(), node.head(), node.tail()))
+            .collect();
+        self
+    }
+
+    /// Highlight the arcs of a critical path from the source to the sink
+    pub fn with_critical_path(mut self) -> Result<Self, GraphError> {
+        let (_, path) = self.graph.critical_path()?;
+        let mut nodes = vec!(self.graph.source().id());
+        nodes.extend(path.iter().map(|n| n.id()));
+        nodes.push(self.graph.sink().id());
+
+        self.critical = nodes.windows(2).map(|w| (w[0], w[1])).collect();
+        Ok(self)
+    }
+
+    pub fn render<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        dot::render(self, &mut buffer)?;
+
+        // The dot crate writes no graph attributes, the layout goes before the closing brace
+        let end = buffer.iter().rposition(|b| *b == b'}').unwrap_or(buffer.len());
+        w.write_all(&buffer[..end])?;
+        writeln!(w, "    rankdir=LR;")?;
+        for rank in self.ranks() {
+            let ids = rank.iter().map(|id| format!("n{}", id)).collect::<Vec<_>>();
+            writeln!(w, "    {{ rank=same; {}; }}", ids.join("; "))?;
+        }
+        writeln!(w, "}}")
+    }
+
+    /// Nodes of the n-th operation of every job
+    fn ranks(&self) -> Vec<Vec<usize>> {
+        let mut positions = Vec::new();
+        let mut ranks: Vec<Vec<usize>> = Vec::new();
+        for node in self.graph.nodes() {
+            if let Some(job) = node.job_id() {
+                if positions.len() <= job {
+                    positions.resize(job + 1, 0);
+                }
+                if ranks.len() <= positions[job] {
+                    ranks.push(Vec::new());
+                }
+                ranks[positions[job]].push(node.id());
+                positions[job] += 1;
+            }
+        }
+        ranks
+    }
+}
+
+impl<'a, 'g, G: Graph> Labeller<'a, usize, Edge> for DotGraph<'g, G> {
+    fn graph_id(&'a self) -> Id<'a> {
+        Id::new("jobshop").unwrap()
+    }
+
+    fn node_id(&'a self, n: &usize) -> Id<'a> {
+        Id::new(format!("n{}", n)).unwrap()
+    }
+
+    fn node_label(&'a self, n: &usize) -> LabelText<'a> {
+        LabelText::label(Cow::Borrowed(self.labels[*n].as_str()))
+    }
+
+    fn node_style(&'a self, n: &usize) -> Style {
+        match self.graph[*n].machine_id() {
+            Some(_) => Style::Filled,
+            None => Style::None
+        }
+    }
+
+    fn node_color(&'a self, n: &usize) -> Option<LabelText<'a>> {
+        self.graph[*n].machine_id().map(|machine| LabelText::label(COLORS[machine as usize % COLORS.len()]))
+    }
+
+    fn edge_style(&'a self, e: &Edge) -> Style {
+        match e.2 {
+            EdgeKind::Disjunction => Style::Dashed,
+            EdgeKind::Precedence if self.critical.contains(&(e.0, e.1)) => Style::Bold,
+            EdgeKind::Precedence => Style::Solid
+        }
+    }
+
+    fn edge_color(&'a self, e: &Edge) -> Option<LabelText<'a>> {
+        if self.critical.contains(&(e.0, e.1)) {
+            Some(LabelText::label("red"))
+        } else {
+            None
+        }
+    }
+
+    fn edge_end_arrow(&'a self, e: &Edge) -> Arrow {
+        match e.2 {
+            EdgeKind::Disjunction => Arrow::none(),
+            EdgeKind::Precedence => Arrow::default()
+        }
+    }
+}
+
+impl<'a, 'g, G: Graph> GraphWalk<'a, usize, Edge> for DotGraph<'g, G> {
+    fn nodes(&'a self) -> Nodes<'a, usize> {
+        self.graph.nodes().iter().map(|n| n.id()).collect()
+    }
+
+    fn edges(&'a self) -> Edges<'a, Edge> {
+        let graph = self.graph;
+        let precedences = graph.nodes().iter()
+            .flat_map(|node| graph.successors(node).map(move |s| (node.id(), s.id(), EdgeKind::Precedence)));
+        // Every disjunction once
+        let disjunctions = graph.nodes().iter()
+            .flat_map(|node| graph.disjunctions(node).map(move |d| (node.id(), d.id(), EdgeKind::Disjunction)))
+            .filter(|(a, b, _)| a < b);
+
+        precedences.chain(disjunctions).collect()
+    }
+
+    fn source(&'a self, e: &Edge) -> usize {
+        e.0
+    }
+
+    fn target(&'a self, e: &Edge) -> usize {
+        e.1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem::Problem;
+    use disjunctgraph::LinkedGraph;
+    use cpbab::Node;
+
+    fn render<G: Graph>(dot: DotGraph<G>) -> String {
+        let mut buffer = Vec::new();
+        dot.render(&mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_graphviz() {
+        // Job 1 takes 3 on machine 1 and 2 on machine 2, job 2 takes 4 on machine 1
+        let problem = Problem::from_reader(r"2
+2
+7
+3 2
+4
+1 2
+1".as_bytes()).unwrap();
+        let mut graph: LinkedGraph<Node> = problem.into_graph();
+        graph.init_weights();
+
+        let output = render(DotGraph::new(&graph).with_windows());
+        assert!(output.starts_with("digraph jobshop {"));
+        assert!(output.contains("n1 -> n2[label=\"\"][style=\"solid\"];"));
+        assert!(output.contains("n1 -> n3[label=\"\"][style=\"dashed\"][arrowhead=\"none\"];"));
+        assert!(output.contains("n1[label=\"1\\np: 3\\nhead: 0, tail: 2\"][style=\"filled\"][color=\"lightpink\"];"));
+        assert!(output.contains("{ rank=same; n1; n3; }"));
+        assert!(output.trim_end().ends_with('}'));
+
+        graph.fix_disjunction(&3, &1).unwrap();
+        let output = render(DotGraph::new(&graph).with_critical_path().unwrap());
+        assert!(output.contains("n3 -> n1[label=\"\"][style=\"bold\"][color=\"red\"];"));
+        assert!(!output.contains("dashed"));
+    }
+}
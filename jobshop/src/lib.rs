@@ -5,7 +5,10 @@ pub mod problem;
 pub mod local_search;
 pub mod cpbab;
 pub mod playbab;
-//pub mod branch_and_bound;
-//pub mod constraints;
+pub mod dichotomic;
+pub mod bidirectional;
+pub mod branch_and_bound;
+pub mod constraints;
+pub mod graphviz;
 pub mod schedule;
 pub mod z3;
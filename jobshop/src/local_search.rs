@@ -1,29 +1,39 @@
+use std::marker::PhantomData;
+
 use disjunctgraph::{ Graph, GraphNode, NodeId };
 
 use crate::problem::{ ProblemSolver, Problem, ProblemNode };
 
 // In the case of a search, it might be nice to only store partial orientations.
 // As described in https://pure.tue.nl/ws/files/2119953/385216.pdf
-pub struct LocalSearch {    
+pub struct LocalSearch<G = disjunctgraph::LinkedGraph<ProblemNode>> {
     temperature: u32,
+    graph: PhantomData<fn() -> G>,
 }
 
-type LinkedGraph = disjunctgraph::LinkedGraph<ProblemNode>;
-
 impl LocalSearch {
     pub fn new(temperature: u32) -> Self {
         LocalSearch {
-            temperature
+            temperature,
+            graph: PhantomData
         }
     }
 }
-impl ProblemSolver for LocalSearch {
-    type Solution = LinkedGraph;
+
+impl<G> LocalSearch<G> {
+    /// Search with another graph representation.
+    pub fn with_graph<H>(self) -> LocalSearch<H> {
+        LocalSearch { temperature: self.temperature, graph: PhantomData }
+    }
+}
+
+impl<G: Graph> ProblemSolver for LocalSearch<G> {
+    type Solution = G;
 
     fn solve(&self, problem: &Problem) -> Self::Solution {                
         use rand::seq::SliceRandom;
 
-        let graph = problem.into_graph::<LinkedGraph>();
+        let graph = problem.into_graph::<G>();
         
         let mut graph = graph.into_directed().expect("Graph was directed, something went wrong check code.");
         let mut no_improvement_cycles = 0;
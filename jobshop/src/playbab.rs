@@ -1,31 +1,71 @@
 use crate::problem::{ ProblemSolver, Problem };
-use disjunctgraph::Graph;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use disjunctgraph::{ ConstrainedNode, Graph };
 
 // In the case of a search, it might be nice to only store partial orientations.
 // As described in https://pure.tue.nl/ws/files/2119953/385216.pdf
-pub struct PlayBAB;
+pub struct PlayBAB<G = playbab::CGraph> {
+    config: playbab::SearchConfig,
+    bidirectional: bool,
+    graph: PhantomData<fn() -> G>,
+}
 
 impl PlayBAB {
     pub fn new() -> Self {
-        PlayBAB {}
+        PlayBAB::with_config(Default::default())
+    }
+
+    pub fn with_config(config: playbab::SearchConfig) -> Self {
+        PlayBAB { config, bidirectional: false, graph: PhantomData }
+    }
+}
+
+impl<G> PlayBAB<G> {
+    /// Search with another graph representation.
+    pub fn with_graph<H>(self) -> PlayBAB<H> {
+        PlayBAB { config: self.config, bidirectional: self.bidirectional, graph: PhantomData }
+    }
+
+    /// Race the search against the search on the reversed problem.
+    pub fn bidirectional(self) -> Self {
+        PlayBAB { bidirectional: true, ..self }
+    }
+
+    pub fn config(&self) -> &playbab::SearchConfig {
+        &self.config
     }
 }
-impl ProblemSolver for PlayBAB {
-    type Solution = playbab::CGraph;
+impl<G: Graph + Clone + Debug + Send + 'static> ProblemSolver for PlayBAB<G> where G::Node: ConstrainedNode + Debug {
+    type Solution = G;
 
     fn solve(&self, problem: &Problem) -> Self::Solution {
+        if self.bidirectional {
+            let config = self.config.clone();
+            return crate::bidirectional::race(problem, move |problem, stop| {
+                PlayBAB::with_config(playbab::SearchConfig { stop: Some(stop), ..config.clone() }).with_graph::<G>().solve(problem)
+            });
+        }
+
         let mm = crate::local_search::LocalSearch::new(5000)
             .solve(problem)
             .critical_length().unwrap();
-        let graph = problem.into_graph();
+        let graph: G = problem.into_graph();
 
         println!("Found local search: {}", mm);
         
-        let solution = playbab::branch_and_bound(graph, problem.machines as usize, 609);//found 579;
-
-        //println!("dbg: {:?}", solution);
-        
-        solution
+        // The local search schedule has makespan mm, so there is always a schedule within it.
+        match playbab::branch_and_bound_with(graph.clone(), mm, &self.config) {
+            Ok(solution) => {
+                println!("Makespan: {}, {}", solution.makespan, solution.stats);
+                solution.graph
+            },
+            Err(e) => {
+                println!("{}", e);
+                graph
+            }
+        }
 
     }
 }
@@ -34,7 +74,7 @@ impl ProblemSolver for PlayBAB {
 #[cfg(test)]
 mod tests {    
     use crate::playbab::PlayBAB;
-    use crate::problem::{ Problem, ProblemSolver };
+    use crate::problem::{ Problem, ProblemSolver, small_problem };
 
     #[test]
     fn test_playbab_1() {
@@ -73,6 +113,48 @@ mod tests {
         assert_eq!(537, l.critical_length().unwrap());
     }
 
+    #[test]
+    fn test_playbab_splits() {
+        use disjunctgraph::Graph;
+        for split in &[playbab::Split::EarliestStart, playbab::Split::SetTimes] {
+            let config = playbab::SearchConfig { split: *split, ..Default::default() };
+            let l = PlayBAB::with_config(config).solve(&small_problem());
+            assert_eq!(13, l.critical_length().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_playbab_implied() {
+        use disjunctgraph::Graph;
+        let config = ::playbab::SearchConfig { implied: true, ..Default::default() };
+        let l = PlayBAB::with_config(config).solve(&small_problem());
+        assert_eq!(13, l.critical_length().unwrap());
+    }
+
+    #[test]
+    fn test_playbab_matrix_graph() {
+        use disjunctgraph::{ Graph, MatrixGraph };
+        let l = PlayBAB::new().with_graph::<MatrixGraph<::playbab::Node>>().solve(&small_problem());
+        assert!(!l.has_disjunctions());
+        assert_eq!(13, l.critical_length().unwrap());
+    }
+
+    #[test]
+    fn test_energetic_reasoning() {
+        use disjunctgraph::{ Graph, GraphError };
+        let problem = debug_problem();
+        let mut graph: ::playbab::CGraph = problem.into_graph();
+        graph.init_weights();
+
+        assert!(disjunctgraph::energetic_reasoning(&[1, 2], &graph, 13).is_ok());
+
+        // On machine 2 within [2, 9] node 2 needs 6 and node 4 needs 2
+        match disjunctgraph::energetic_reasoning(&[1, 2], &graph, 12) {
+            Err(GraphError::Infeasible) => (),
+            other => panic!("Expected infeasible, got {:?}", other)
+        }
+    }
+
     /*#[bench]
     fn bench_local_search_small(b: &mut Bencher) {
         use crate::local_search::LocalSearch;
@@ -100,18 +182,6 @@ mod tests {
 1 2 1
 2 1".as_bytes()).unwrap()
     }
-    fn small_problem() -> Problem {
-        Problem::from_reader(r"3
-3
-13
-3 2 3
-3 4
-6 3 2
-1 2 3
-3 2
-2 1 3".as_bytes()).unwrap()
-    }
-
     fn big_problem() -> Problem {
         Problem::from_reader(r"10
 5
@@ -2,15 +2,16 @@ use std::io::BufReader;
 use std::fs::File;
 use std::path::Path;
 
-use disjunctgraph::{ Graph, GraphNode, Relation };
+use disjunctgraph::{ Graph, GraphError, GraphNode, NodeId, Relation };
 use itertools::Itertools;
+use serde_derive::{ Serialize, Deserialize };
 
 pub trait ProblemSolver {
     type Solution;
     fn solve(&self, problem: &Problem) -> Self::Solution;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Problem {
     pub machines: u32,
     pub optimal: u32,
@@ -18,7 +19,7 @@ pub struct Problem {
     pub jobs: Vec<Vec<usize>>,    
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Activity {
     pub id: usize,
     pub process_time: u32,
@@ -85,6 +86,57 @@ impl Problem {
         Problem::from_reader(reader)        
     }
 
+    /// The mirrored problem: every job is processed in reverse order.
+    /// A schedule of the reverse read backwards from its makespan is a schedule of the original
+    /// with the same makespan. Activities are renumbered to stay in job order, see `reversed_activity`.
+    pub fn reversed(&self) -> Problem {
+        let mut activities = self.activities.clone();
+        for job in &self.jobs {
+            let start = job[0];
+            for (i, activity) in job.iter().rev().enumerate() {
+                activities[start + i] = Activity {
+                    id: start + i,
+                    precedences: (start..(start + i)).collect(),
+                    ..self.activities[*activity].clone()
+                };
+            }
+        }
+
+        Problem {
+            machines: self.machines,
+            optimal: self.optimal,
+            activities,
+            jobs: self.jobs.clone(),
+        }
+    }
+
+    /// The id of an activity in the reversed problem, also maps back as reversing twice is the identity.
+    pub fn reversed_activity(&self, activity: usize) -> usize {
+        let job = self.jobs.iter().find(|job| job.contains(&activity)).expect("Activity is not part of a job");
+        job[0] + job[job.len() - 1] - activity
+    }
+
+    /// Orient the graph of this problem as a solution of `self.reversed()` is oriented.
+    /// Every fixed disjunction a -> b of the reverse becomes b -> a, heads and tails are not initialized.
+    pub fn from_reversed<I: Graph>(&self, reversed: &I) -> Result<I, GraphError> {
+        let mut graph: I = self.into_graph();
+        let sink = graph.sink().id();
+        let original = |node: usize| if node == 0 || node == sink { node } else { self.reversed_activity(node - 1) + 1 };
+
+        let arcs = reversed.nodes().iter()
+            .flat_map(|node| reversed.successors(node).map(move |other| (node.id(), other.id())))
+            .collect_vec();
+
+        for (a, b) in arcs {
+            let (a, b) = (original(a), original(b));
+            if graph.has_disjunction(&b, &a) {
+                graph.fix_disjunction(&b, &a)?;
+            }
+        }
+
+        Ok(graph)
+    }
+
     pub fn into_graph<I: Graph>(&self) -> I {
         let problem = self;
 	    // Create nodes
@@ -173,7 +225,7 @@ impl Problem {
 }
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ProblemNode {
     id: usize,
     job_id: Option<usize>,
@@ -197,3 +249,62 @@ impl disjunctgraph::GraphNode for ProblemNode {
 }
 
 
+/// Three jobs on three machines with an optimal makespan of 13, shared by the tests of the solvers.
+#[cfg(test)]
+pub(crate) fn small_problem() -> Problem {
+    Problem::from_reader(r"3
+3
+13
+3 2 3
+3 4
+6 3 2
+1 2 3
+3 2
+2 1 3".as_bytes()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use disjunctgraph::{ ConstrainedNode, LinkedGraph };
+
+    #[test]
+    fn test_serialize_graph() {
+        let problem = Problem::from_reader(r"2
+2
+7
+3 2
+4
+1 2
+1".as_bytes()).unwrap();
+        let mut graph: LinkedGraph<cpbab::Node> = problem.into_graph();
+        let (node_1, node_2) = graph.nodes().iter()
+            .flat_map(|node| graph.disjunctions(node).map(move |other| (node.id(), other.id())))
+            .next().unwrap();
+        // A disjunction that only one of its nodes knows about
+        let mut one_sided = serde_json::to_value(&graph).unwrap();
+        one_sided["disjunctions"][node_1].as_array_mut().unwrap().retain(|other| *other != node_2);
+        assert!(serde_json::from_value::<LinkedGraph<cpbab::Node>>(one_sided).is_err());
+
+        graph.fix_disjunction(&node_1, &node_2).unwrap();
+        graph.init_weights();
+
+        // Fixing the disjunction both ways closes a cycle
+        let mut cyclic = serde_json::to_value(&graph).unwrap();
+        cyclic["successors"][node_2].as_array_mut().unwrap().push(node_1.into());
+        cyclic["fixed"][node_2].as_array_mut().unwrap().push(node_1.into());
+        let error = serde_json::from_value::<LinkedGraph<cpbab::Node>>(cyclic).err().unwrap();
+        assert!(error.to_string().contains("Cyclic"));
+
+        let json = serde_json::to_string(&graph).unwrap();
+        let loaded: LinkedGraph<cpbab::Node> = serde_json::from_str(&json).unwrap();
+        assert_eq!(json, serde_json::to_string(&loaded).unwrap());
+        assert_eq!(graph.selection_hash(), loaded.selection_hash());
+        assert!(loaded.has_fixed_disjunction(&node_1, &node_2));
+        assert!(loaded.nodes().iter().all(|node| node.head() == graph[node.id()].head() && node.tail() == graph[node.id()].tail()));
+        assert!(loaded.predecessors(&node_2).any(|node| node.id() == node_1));
+
+        let loaded: Problem = serde_json::from_str(&serde_json::to_string(&problem).unwrap()).unwrap();
+        assert_eq!(problem.activities, loaded.activities);
+    }
+}
@@ -3,6 +3,9 @@ use crate::problem::{ Activity, Problem };
 use disjunctgraph::{Graph, NodeId, GraphNode };
 use itertools::Itertools;
 
+mod sequences;
+pub use sequences::MachineSequences;
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct ScheduledActivity {
     pub activity: Activity,
@@ -0,0 +1,132 @@
+use crate::problem::Problem;
+use super::Schedule;
+
+use disjunctgraph::{ Graph, GraphError, GraphNode, NodeId };
+use itertools::Itertools;
+
+/// The order in which every machine processes its activities.
+/// Machine `m` is at index `m - 1`, activities are the ids of the problem.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachineSequences {
+    pub machines: Vec<Vec<usize>>
+}
+
+impl MachineSequences {
+    /// Orient the graph of the problem such that every machine follows its sequence.
+    /// Errors with `InvalidEdge` when a sequence is not an order of the activities on its machine,
+    /// and with `Cyclic` when the sequences contradict each other or the jobs.
+    pub fn into_graph<I: Graph>(&self, problem: &Problem) -> Result<I, GraphError> {
+        if self.machines.len() != problem.machines as usize {
+            return Err(GraphError::InvalidEdge);
+        }
+
+        for (machine, sequence) in self.machines.iter().enumerate() {
+            let activities = problem.activities.iter()
+                .filter(|a| a.machine_id as usize == machine + 1)
+                .map(|a| a.id)
+                .sorted();
+            if !activities.eq(sequence.iter().cloned().sorted()) {
+                return Err(GraphError::InvalidEdge);
+            }
+        }
+
+        let mut graph: I = problem.into_graph();
+        for sequence in &self.machines {
+            // Every pair, the order of activities of one job is already in the graph
+            for (a, b) in sequence.iter().map(|a| a + 1).tuple_combinations() {
+                if graph.has_disjunction(&a, &b) {
+                    graph.fix_disjunction(&a, &b)?;
+                } else if graph.has_precedence(&b, &a) {
+                    return Err(GraphError::Cyclic(vec!(a, b)));
+                }
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// The machine sequences of an oriented graph, errors with `InvalidEdge` while disjunctions are left.
+    pub fn from_graph<I: Graph>(graph: &I) -> Result<Self, GraphError> {
+        if graph.has_disjunctions() {
+            return Err(GraphError::InvalidEdge);
+        }
+        if let Some(cycle) = graph.find_cycle() {
+            return Err(GraphError::Cyclic(cycle));
+        }
+
+        let machines = graph.nodes().iter().filter_map(|n| n.machine_id()).max().unwrap_or(0);
+        let mut sequences = vec!(Vec::new(); machines as usize);
+        for node in graph.topology() {
+            if let Some(machine) = node.machine_id() {
+                sequences[machine as usize - 1].push(node.id() - 1);
+            }
+        }
+
+        Ok(MachineSequences { machines: sequences })
+    }
+}
+
+impl From<&Schedule> for MachineSequences {
+    /// Activities of a machine in order of their starting times
+    fn from(schedule: &Schedule) -> MachineSequences {
+        let machines = schedule.activities.iter().map(|a| a.activity.machine_id).max().unwrap_or(0);
+        let mut sequences = vec!(Vec::new(); machines as usize);
+        for scheduled in schedule.activities.iter().sorted_by_key(|a| (a.starting_time, a.activity.id)) {
+            sequences[scheduled.activity.machine_id as usize - 1].push(scheduled.activity.id);
+        }
+
+        MachineSequences { machines: sequences }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem::ProblemNode;
+    use disjunctgraph::LinkedGraph;
+
+    #[test]
+    fn test_machine_sequences() {
+        // Job 1 takes 3 on machine 1 and 2 on machine 2, job 2 takes 4 on machine 1
+        let problem = Problem::from_reader(r"2
+2
+7
+3 2
+4
+1 2
+1".as_bytes()).unwrap();
+
+        let sequences = MachineSequences { machines: vec!(vec!(0, 2), vec!(1)) };
+        let graph: LinkedGraph<ProblemNode> = sequences.into_graph(&problem).unwrap();
+        assert!(graph.has_precedence(&1, &3));
+        assert_eq!(7, graph.critical_length().unwrap());
+        assert_eq!(sequences, MachineSequences::from_graph(&graph).unwrap());
+        assert_eq!(sequences, MachineSequences::from(&Schedule::from_graph(problem.clone(), graph)));
+
+        let sequences = MachineSequences { machines: vec!(vec!(2, 0), vec!(1)) };
+        let graph: LinkedGraph<ProblemNode> = sequences.into_graph(&problem).unwrap();
+        assert_eq!(9, graph.critical_length().unwrap());
+
+        let missing = MachineSequences { machines: vec!(vec!(2), vec!(1)) };
+        assert!(missing.into_graph::<LinkedGraph<ProblemNode>>(&problem).is_err());
+        assert!(MachineSequences::from_graph(&problem.into_graph::<LinkedGraph<ProblemNode>>()).is_err());
+    }
+
+    #[test]
+    fn test_machine_sequences_cycle() {
+        // Both jobs visit the machines in opposite order
+        let problem = Problem::from_reader(r"2
+2
+2
+1 1
+1 1
+1 2
+2 1".as_bytes()).unwrap();
+
+        let sequences = MachineSequences { machines: vec!(vec!(3, 0), vec!(1, 2)) };
+        match sequences.into_graph::<LinkedGraph<ProblemNode>>(&problem) {
+            Err(GraphError::Cyclic(cycle)) => assert_eq!(4, cycle.len()),
+            other => panic!("Expected a cycle, got {:?}", other.map(|_| ()))
+        }
+    }
+}
@@ -33,7 +33,7 @@ impl ProblemSolver for Z3Solve {
 #[cfg(test)]
 mod tests {    
     use crate::z3::Z3Solve;
-    use crate::problem::{ Problem, ProblemSolver };
+    use crate::problem::{ Problem, ProblemSolver, small_problem };
 
     #[test]
     fn z3solver_1() {
@@ -83,18 +83,6 @@ mod tests {
 1 2 1
 2 1".as_bytes()).unwrap()
     }
-    fn small_problem() -> Problem {
-        Problem::from_reader(r"3
-3
-13
-3 2 3
-3 4
-6 3 2
-1 2 3
-3 2
-2 1 3".as_bytes()).unwrap()
-    }
-
     fn dmu03_rcmax_20_15_5() -> Problem {
         Problem::from_reader(r"20
 15
@@ -3,169 +3,235 @@
 extern crate disjunctgraph;
 
 mod node;
-mod task_interval;
 mod propagation;
+mod split;
 
 use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicBool, Ordering };
 
-use disjunctgraph::{ GraphNode, ConstrainedNode, NodeId, Graph };
-use itertools::Itertools;
+use disjunctgraph::{ GraphNode, ConstrainedNode, Graph };
+use disjunctgraph::transposition::{ self, TranspositionTable };
+
+pub use split::Split;
+pub use node::Node;
 
 // Constrained graph ;
-pub type CGraph = disjunctgraph::LinkedGraph<node::Node>;
+pub type CGraph = disjunctgraph::LinkedGraph<Node>;
+
+/// Options for the branch and bound search
+#[derive(Clone, Debug, Default)]
+pub struct SearchConfig {
+    /// Number of slots in the transposition table, 0 disables it.
+    pub transpositions: usize,
+    /// How the start windows are split
+    pub split: Split,
+    /// Fix disjunctions whose order is implied by a path of precedences, so propagation sees them as arcs.
+    pub implied: bool,
+    /// The search returns the best schedule so far once this is set, from another thread.
+    pub stop: Option<Arc<AtomicBool>>,
+}
 
-type TaskInterval<'a> = task_interval::TaskInterval<'a, CGraph>;
+/// Counters of a search
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub nodes: usize,
+    /// Branches that propagation proved infeasible
+    pub failures: usize,
+    /// Nodes pruned by the set-times dominance rules
+    pub dominated: usize,
+    pub solutions: usize,
+    pub transpositions: usize,
+}
 
-const PAR: u32 = 3;
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Node evaluations: {}, failures: {}, dominated: {}, solutions: {}, transpositions: {}",
+            self.nodes, self.failures, self.dominated, self.solutions, self.transpositions)
+    }
+}
 
-// What is still needed?
-// There is no propagation of constraints,
-// Operations that have no disjunctions left are looked at too.
-// I believe cycles can occur? ()
-pub fn branch_and_bound(mut root: CGraph, resources: usize, max_makespan: u32) -> CGraph {
-    root.init_weights();//.expect("Problem with makespan is not feasible");    
-    dbg!(crate::propagation::search_orders(&mut root, max_makespan).unwrap());
+/// The best schedule found, it is optimal when the search was not interrupted.
+#[derive(Clone, Debug)]
+pub struct Solution<G> {
+    pub graph: G,
+    pub makespan: u32,
+    pub stats: Stats,
+}
 
-    let resources = (1..=resources).collect::<Vec<_>>();
-    for resource in &resources {
-        crate::propagation::edge_finding(*resource as u32, &mut root, max_makespan).unwrap();
-    }
-    //root.init_weights();
-    //root.search_orders(max_makespan);
+struct SearchNode<G> {
+    graph: G,
+    postponed: Vec<split::Postponed>,
+}
+
+pub fn branch_and_bound<G: Graph + Clone + Debug>(root: G, max_makespan: u32) -> Result<Solution<G>, String> where G::Node: ConstrainedNode + Debug {
+    branch_and_bound_with(root, max_makespan, &SearchConfig::default())
+}
+
+/// Search for the schedule with the smallest makespan of at most `max_makespan`.
+/// Errors when there is no such schedule.
+///
+/// A split restricting the start of an operation to at most t is stored as the tail ub - t - p,
+/// which is only a deadline for that upper bound. So every improvement starts a new search
+/// from the root with the smaller upper bound, until no schedule is found.
+pub fn branch_and_bound_with<G: Graph + Clone + Debug>(mut root: G, max_makespan: u32, config: &SearchConfig) -> Result<Solution<G>, String> where G::Node: ConstrainedNode + Debug {
     root.init_weights();
-    
-    //println!("{:?}", root);
+    propagation::propagate(&mut root, max_makespan)
+        .map_err(|e| format!("Root is infeasible: {}", e))?;
+
     let mut upper_bound = max_makespan;
-    let mut current_best = root.clone();
+    let mut best: Option<(G, u32)> = None;
 
-    let mut stack: VecDeque<CGraph> = VecDeque::new();
-    stack.push_front(root);
+    let mut transpositions = TranspositionTable::new(config.transpositions);
+    let mut stats = Stats::default();
 
-    
-    let mut node_evaluations = 0;
-    while let Some(node) = stack.pop_front() {        
-        node_evaluations += 1;
+    loop {
+        let mut start = root.clone();
+        if propagation::propagate(&mut start, upper_bound).is_err() {
+            break;
+        }
+
+        match search(start, upper_bound, config, usize::MAX, &mut transpositions, &mut stats) {
+            Outcome::Feasible(graph, length) => {
+                best = Some((graph, length));
+
+                // Only look for strictly better schedules, nothing beats an empty schedule.
+                match length.checked_sub(1) {
+                    Some(bound) => upper_bound = bound,
+                    None => break
+                }
+                // Stored states were searched for the old upper bound only.
+                transpositions.clear();
+            },
+            _ => break
+        }
+    }
+
+    stats.transpositions = transpositions.hits();
+
+    match best {
+        Some((graph, makespan)) => Ok(Solution { graph, makespan, stats }),
+        None => Err(format!("No schedule with a makespan of at most {}, {}", max_makespan, stats))
+    }
+}
+
+/// Result of the decision version of the search
+#[derive(Clone, Debug)]
+pub enum Outcome<G> {
+    /// A schedule and its makespan, which is at most the asked makespan
+    Feasible(G, u32),
+    /// There is no schedule with at most the asked makespan
+    Infeasible,
+    /// The budget ran out before either was shown
+    Unknown,
+}
+
+/// Decision version of the search: is there a schedule with a makespan of at most `makespan`?
+/// Stops at the first schedule found or after `budget` node evaluations.
+pub fn decide<G: Graph + Clone + Debug>(mut root: G, makespan: u32, budget: usize, config: &SearchConfig) -> (Outcome<G>, Stats) where G::Node: ConstrainedNode + Debug {
+    let mut stats = Stats::default();
+    root.init_weights();
+    if propagation::propagate(&mut root, makespan).is_err() {
+        return (Outcome::Infeasible, stats);
+    }
+
+    let mut transpositions = TranspositionTable::new(config.transpositions);
+    let outcome = search(root, makespan, config, budget, &mut transpositions, &mut stats);
+    stats.transpositions = transpositions.hits();
+    (outcome, stats)
+}
+
+/// Depth first search for a complete schedule within the upper bound.
+fn search<G: Graph + Clone + Debug>(root: G, upper_bound: u32, config: &SearchConfig, budget: usize, transpositions: &mut TranspositionTable, stats: &mut Stats) -> Outcome<G> where G::Node: ConstrainedNode + Debug {
+    let mut stack: VecDeque<SearchNode<G>> = VecDeque::new();
+    stack.push_front(SearchNode { graph: root, postponed: Vec::new() });
+
+    let mut evaluations = 0;
+    while let Some(SearchNode { graph: node, mut postponed }) = stack.pop_front() {
+        if evaluations >= budget || matches!(&config.stop, Some(stop) if stop.load(Ordering::Relaxed)) {
+            return Outcome::Unknown;
+        }
+        evaluations += 1;
+        stats.nodes += 1;
+
+        split::release(&node, &mut postponed);
+        if split::dominated(&node, upper_bound, &postponed) {
+            stats.dominated += 1;
+            continue;
+        }
+
+        // Different splits can lead to the same selection and time windows, postponements are part of the state.
+        let hash = postponed.iter()
+            .fold(transposition::state_hash(&node), |hash, (id, head)| hash ^ transposition::window_key(*id, *head, u32::MAX));
+        if transpositions.visit(hash, upper_bound) {
+            continue;
+        }
 
         // Check if graph has disjunctions left.
         if !node.has_disjunctions() {
             // We are a complete schedule!
-            
-            let length = dbg!(node.critical_length().expect("Could not calculate critical length"));
-            let lb = lower_bound(&node, upper_bound, &resources);
-            if length <= upper_bound {
-                upper_bound = length;
-                current_best = node;
+            match node.critical_length() {
+                Ok(length) if length <= upper_bound => {
+                    stats.solutions += 1;
+                    return Outcome::Feasible(node, length);
+                },
+                _ => stats.failures += 1
             }
+            continue;
+        }
 
-            println!("We got one of length: {} or is it {}?", length, lb);
-        } else {
-            if lower_bound(&node, upper_bound, &resources) > upper_bound {                
+        let branches = match split::branches(config.split, &node, upper_bound, &postponed) {
+            Ok(branches) => branches,
+            Err(_) => {
+                stats.failures += 1;
                 continue;
             }
-            //println!("Disjunctions left: {}", node.total_disjunctions());
-            if let Ok(pairs) = next_pair(&resources, &node, upper_bound) {
-                for (free_node, (head, tail)) in pairs {
-                    if head + free_node.weight() + tail > upper_bound {
-                        continue;
-                    }
-                    
+        };
+
+        // The first branch is explored first
+        for branch in branches.into_iter().rev() {
+            match branch {
+                split::Branch::Window { node: id, head, tail } => {
                     let mut graph = node.clone();
+                    graph[id].set_tail(tail);
+                    graph[id].set_head(head);
+
+                    let result = propagation::propagate_head(&id, &mut graph, upper_bound)
+                        .and_then(|_| propagation::propagate_tail(&id, &mut graph, upper_bound))
+                        .and_then(|_| propagation::propagate(&mut graph, upper_bound))
+                        .and_then(|_| fix_implied(&mut graph, config));
 
-                    println!("Total slack before: {}", graph.nodes().iter().map(|n| {
-                        upper_bound - n.head() - n.weight() - n.tail()
-                    }).sum::<u32>());
-
-                    graph[free_node.id()].set_tail(tail);
-                    graph[free_node.id()].set_head(head);
-                    
-                    let r1 = propagation::propagate_head(free_node, &mut graph, upper_bound)
-                        .and_then(|_| propagation::propagate_tail(free_node, &mut graph, upper_bound))
-                        .and_then(|_| {
-                            while propagation::search_orders(&mut graph, upper_bound)? {}
-                            Ok(())
-                        });
-                        
-                    
-                    match r1 {
-                        Ok(_) => {
-
-                            println!("Total slack after: {}", graph.nodes().iter().map(|n| {
-                                upper_bound - n.head() - n.weight() - n.tail()
-                            }).sum::<u32>());
-                            if lower_bound(&graph, max_makespan, &resources) <= upper_bound {
-                                stack.push_front(graph);                            
-                            }
+                    match result {
+                        Ok(_) if lower_bound(&graph) <= upper_bound => {
+                            stack.push_front(SearchNode { graph, postponed: postponed.clone() });
                         },
-                        Err(e1) => println!("Error: {:?}", e1)
+                        _ => stats.failures += 1
                     }
+                },
+                split::Branch::Postpone { node: id } => {
+                    let mut postponed = postponed.clone();
+                    postponed.push((id, node[id].head()));
+                    stack.push_front(SearchNode { graph: node.clone(), postponed });
                 }
             }
         }
     }
-    println!("Node evaluations: {}", node_evaluations);
-    current_best
-}
-
-
 
-fn next_pair<'a>(resources: &[usize], graph: &'a CGraph, upper_bound: u32) -> Result<Vec<(&'a node::Node, (u32, u32))>, String> {
-    // Naive shit to do it.
-    // We take the resource with most freedom and many successors/predecessors.
-    // freedom * (successors + predecessors)
-    let free_node = graph.nodes().iter().max_by_key(|n| {
-        let freedom = upper_bound - n.tail() - n.weight() - n.head();
-        let successors = graph.successors(&n.id())
-            .filter(|o| n.head() + (freedom >> 1) > o.head()) // if my head changes, successors head changes
-            .count() as u32;
-        let predecessors = graph.predecessors(&n.id())
-            .filter(|o| n.tail() + (freedom >> 1) > o.tail()) // if my head changes, successors head changes
-            .count() as u32;
-        //let disjunctions = graph.disjunctions(&n.id()).count() as u32;
-        freedom * (successors + predecessors)// * disjunctions
-    }).unwrap();
-
-    let head = free_node.head();
-    let tail = free_node.tail();
-
-    let freedom = (upper_bound - free_node.tail()) - free_node.weight() - free_node.head();
-    
-    let new_tail = tail + freedom >> 1;
-    let new_head = head + freedom >> 1;
-
-    // We want to split in two for now,
-    // [est, (est + lct) / 2]
-    // [(est + lct) / 2, lct]
+    Outcome::Infeasible
+}
 
-    // [head, (est + ub - tail) / 2]
-    // 
 
-    Ok(vec!((free_node, (head, new_tail + free_node.weight())), (free_node, (new_head, tail))))
+fn fix_implied<G: Graph>(graph: &mut G, config: &SearchConfig) -> Result<(), String> {
+    if config.implied {
+        disjunctgraph::remove_implied(graph).map_err(|e| format!("Could not fix implied disjunctions, {:?}", e))?;
+    }
+    Ok(())
 }
 
-
 /// According to: Adjustment of heads and tails for the job-shop problem (J. Carlier and E. Pinson)
 /// Chapter 4.4: Lower bound
-/// Warning: Does not implement all three bounds.
-/// A study of lower bounds is acceptable
-fn lower_bound(graph: &CGraph, upper_bound: u32, resources: &[usize]) -> u32 {    
-    let resources = resources.iter()
-        .map(|resource| graph.nodes().iter().filter(move |n| n.machine_id() == Some(*resource as u32))) // Returns an I_k on machine M_k
-        .collect::<Vec<_>>();
-        
-    let d2 = graph.nodes().iter().map(|n| n.head() + n.weight() + n.tail()).max().unwrap();
-    /*let d1 = resources.into_iter()
-        .flat_map(|resource| resource.combinations(2)) // Take all combinations of 2
-        .map(|comb| {
-            let n1 = &comb[0];
-            let n2 = &comb[1];
-
-            let a = n1.head() + n1.weight() + n2.weight() + n2.tail();
-            let b = n2.head() + n2.weight() + n1.weight() + n2.tail();
-            std::cmp::max(a, b)
-        })        
-        .filter(|d| d <= &upper_bound)
-        .max().unwrap_or(d2 + 1);*/
-
-    //std::cmp::max(d1 - 1, d2)
-    d2
+/// Warning: Only the bound of a single operation, head + processing + tail, is used.
+fn lower_bound<G: Graph>(graph: &G) -> u32 where G::Node: ConstrainedNode {
+    graph.nodes().iter().map(|n| n.head() + n.weight() + n.tail()).max().unwrap_or(0)
 }
@@ -0,0 +1,136 @@
+//! Branching on the start time windows of operations.
+//!
+//! Every branch restricts the window [head, lst] of one operation, propagation
+//! does the rest. Set-times branching (C. Le Pape, P. Couronné, D. Vergamini and V. Gosselin)
+//! either starts the operation with the earliest head at that head or postpones it,
+//! a postponed operation can not be selected again until propagation increases its head.
+use disjunctgraph::{ Graph, ConstrainedNode, GraphNode, NodeId };
+
+/// How the window of an operation is split
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Split {
+    /// Halve the window of the operation with most freedom and impact on its neighbours.
+    #[default]
+    Bisection,
+    /// Start the operation with the earliest head at its head, or at least one later.
+    EarliestStart,
+    /// Start the operation with the earliest head at its head, or postpone it.
+    SetTimes,
+}
+
+/// A child of a search node
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Branch {
+    /// Restrict the node to the new head and tail.
+    Window { node: usize, head: u32, tail: u32 },
+    /// Do not select the node again until its head increases.
+    Postpone { node: usize },
+}
+
+/// A postponed node and its head at the moment it was postponed
+pub type Postponed = (usize, u32);
+
+/// The branches of a node in the order they should be explored.
+/// Errors when no operation is left to branch on or an operation does not fit in the upper bound.
+pub fn branches<G: Graph>(split: Split, graph: &G, upper_bound: u32, postponed: &[Postponed]) -> Result<Vec<Branch>, String> where G::Node: ConstrainedNode {
+    if graph.nodes().iter().any(|n| slack(n, upper_bound).is_none()) {
+        return Err("An operation has a negative slack".to_string());
+    }
+
+    match split {
+        Split::Bisection => {
+            let (node, freedom) = most_free(graph, upper_bound).ok_or("No operation left to split")?;
+            let (head, weight) = (node.head(), node.weight());
+            let middle = head + freedom / 2;
+
+            // [head, middle] and [middle + 1, lst]
+            Ok(vec!(
+                Branch::Window { node: node.id(), head, tail: upper_bound - middle - weight },
+                Branch::Window { node: node.id(), head: middle + 1, tail: node.tail() }))
+        },
+        Split::EarliestStart => {
+            let node = earliest(graph, upper_bound, &[]).ok_or("No operation left to split")?;
+            let (head, weight) = (node.head(), node.weight());
+
+            Ok(vec!(
+                Branch::Window { node: node.id(), head, tail: upper_bound - head - weight },
+                Branch::Window { node: node.id(), head: head + 1, tail: node.tail() }))
+        },
+        Split::SetTimes => {
+            let node = earliest(graph, upper_bound, postponed).ok_or("Only postponed operations left")?;
+            let (head, weight) = (node.head(), node.weight());
+
+            Ok(vec!(
+                Branch::Window { node: node.id(), head, tail: upper_bound - head - weight },
+                Branch::Postpone { node: node.id() }))
+        }
+    }
+}
+
+/// Forget postponements of nodes whose head was increased by propagation.
+pub fn release<G: Graph>(graph: &G, postponed: &mut Vec<Postponed>) where G::Node: ConstrainedNode {
+    postponed.retain(|(node, head)| graph[*node].head() == *head);
+}
+
+/// Dominance rules of set-times, true when the node can be pruned.
+///
+/// A postponed operation has to start later than its head, only selecting another operation
+/// can push it there. No selectable operation starts before the smallest head S among them, so:
+/// 1. if a postponed operation ends before S it could have started at its head, the node is dominated,
+/// 2. if a postponed operation has to start before S it can not be delayed at all.
+pub fn dominated<G: Graph>(graph: &G, upper_bound: u32, postponed: &[Postponed]) -> bool where G::Node: ConstrainedNode {
+    if postponed.is_empty() {
+        return false;
+    }
+
+    let selectable = graph.nodes().iter()
+        .filter(|n| is_free(*n, upper_bound) && !postponed.iter().any(|(p, _)| *p == n.id()))
+        .map(|n| n.head())
+        .min();
+
+    match selectable {
+        // Postponed operations are left that nothing can delay
+        None => true,
+        Some(start) => postponed.iter()
+            .map(|(node, _)| &graph[*node])
+            .any(|n| n.head() + n.weight() <= start || n.lst(upper_bound) < start)
+    }
+}
+
+/// An operation of which the start time is not yet fixed.
+fn is_free<N: ConstrainedNode>(node: &N, upper_bound: u32) -> bool {
+    node.machine_id().is_some() && slack(node, upper_bound).is_some_and(|s| s > 0)
+}
+
+/// Room of the node within the upper bound, None when it does not fit.
+fn slack<N: ConstrainedNode>(node: &N, upper_bound: u32) -> Option<u32> {
+    upper_bound.checked_sub(node.head())?
+        .checked_sub(node.weight())?
+        .checked_sub(node.tail())
+}
+
+/// Free operation with the most freedom times the number of neighbours that
+/// are affected when half of its window is cut off, together with its slack.
+fn most_free<G: Graph>(graph: &G, upper_bound: u32) -> Option<(&G::Node, u32)> where G::Node: ConstrainedNode {
+    graph.nodes().iter()
+        .filter(|n| n.machine_id().is_some())
+        .filter_map(|n| slack(n, upper_bound).filter(|s| *s > 0).map(|s| (n, s)))
+        .max_by_key(|&(n, freedom)| {
+            // If my head changes, the heads of my successors change
+            let successors = graph.successors(&n.id())
+                .filter(|o| n.head() + n.weight() + (freedom >> 1) > o.head())
+                .count() as u32;
+            // If my tail changes, the tails of my predecessors change
+            let predecessors = graph.predecessors(&n.id())
+                .filter(|o| n.tail() + n.weight() + (freedom >> 1) > o.tail())
+                .count() as u32;
+            (freedom * (successors + predecessors), freedom)
+        })
+}
+
+/// Free operation that is not postponed with the smallest head, ties broken by the smallest latest start.
+fn earliest<'a, G: Graph>(graph: &'a G, upper_bound: u32, postponed: &[Postponed]) -> Option<&'a G::Node> where G::Node: ConstrainedNode {
+    graph.nodes().iter()
+        .filter(|n| is_free(*n, upper_bound) && !postponed.iter().any(|(p, _)| *p == n.id()))
+        .min_by_key(|n| (n.head(), n.lst(upper_bound)))
+}